@@ -0,0 +1,216 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::Package;
+use chrono::Utc;
+use colored::*;
+use std::{
+    error::Error,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::{Path, PathBuf},
+    process::{Command, Stdio},
+    sync::{Arc, Mutex, OnceLock},
+    thread,
+};
+
+/// build logs kept for each package; older ones are deleted.
+const KEEP_LOGS: usize = 5;
+
+/// lines of the log shown when a build fails.
+const TAIL_LINES: usize = 20;
+
+static KEEP_BUILD: OnceLock<bool> = OnceLock::new();
+
+/// set `--keep-build` for this run.
+pub fn init(keep_build: bool) {
+    let _ = KEEP_BUILD.set(keep_build);
+}
+
+fn dir() -> PathBuf {
+    Package::rade_home().join("log/build")
+}
+
+/// copy everything from `from` to `to` and to the log.
+fn tee(mut from: impl Read, mut to: impl Write, log: Arc<Mutex<File>>) {
+    let mut buf = [0; 4096];
+    while let Ok(n) = from.read(&mut buf) {
+        if n == 0 {
+            break;
+        }
+        let _ = to.write_all(&buf[..n]);
+        let _ = to.flush();
+        let _ = log
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .write_all(&buf[..n]);
+    }
+}
+
+/// # run
+/// run `sh install.sh` in `build` for `package`. The output is shown as usual
+/// and also written to `log/build/<package>-<time>.log`, whose path is returned.
+/// A failed build is reported with the end of its log and returned as an error.
+pub fn run(package: &str, build: &Path) -> Result<PathBuf, Box<dyn Error>> {
+    fs::create_dir_all(dir())?;
+    let path = dir().join(format!(
+        "{}-{}.log",
+        package,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    let log = Arc::new(Mutex::new(File::create(&path)?));
+    let mut child = Command::new("sh")
+        .arg(build.join("install.sh"))
+        .current_dir(build)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let stdout = child.stdout.take().ok_or("no stdout")?;
+    let stderr = child.stderr.take().ok_or("no stderr")?;
+    let out = {
+        let log = Arc::clone(&log);
+        thread::spawn(move || tee(stdout, io::stdout(), log))
+    };
+    let err = {
+        let log = Arc::clone(&log);
+        thread::spawn(move || tee(stderr, io::stderr(), log))
+    };
+    let status = child.wait()?;
+    let _ = out.join();
+    let _ = err.join();
+    prune(package);
+
+    if status.success() {
+        return Ok(path);
+    }
+    let log = fs::read_to_string(&path).unwrap_or_default();
+    let lines: Vec<&str> = log.lines().collect();
+    eprintln!(
+        "\n{} {} ({})",
+        ">>>".red().bold(),
+        "install.sh failed".bold(),
+        status
+    );
+    if !lines.is_empty() {
+        eprintln!(
+            "{}",
+            format!("--- last {} lines ---", TAIL_LINES.min(lines.len())).bold()
+        );
+        for line in &lines[lines.len().saturating_sub(TAIL_LINES)..] {
+            eprintln!("{}", line);
+        }
+        eprintln!("{}", "---".bold());
+    }
+    eprintln!("Full log: {}", path.display());
+    Err(format!("install.sh failed; see {}", path.display()).into())
+}
+
+/// where `--keep-build` moves failed builds, outside the scratch `build/` directory.
+fn kept_dir() -> PathBuf {
+    Package::rade_home().join("kept-builds")
+}
+
+/// remove the build directory of a failed build of `package`.
+/// With `--keep-build` it is moved to `kept-builds/<package>-<time>` instead,
+/// so the next install and `rade doctor --fix` leave it alone.
+pub fn discard(package: &str, build: &Path) {
+    if !KEEP_BUILD.get().copied().unwrap_or_default() {
+        let _ = fs::remove_dir_all(build);
+        return;
+    }
+    let kept = kept_dir().join(format!(
+        "{}-{}",
+        package,
+        Utc::now().format("%Y%m%dT%H%M%S")
+    ));
+    match fs::create_dir_all(kept_dir()).and_then(|()| fs::rename(build, &kept)) {
+        Ok(()) => eprintln!("Build directory kept at {}", kept.display()),
+        Err(e) => {
+            eprintln!(
+                "{} Failed to move the build directory to {}: {}",
+                ">>>".yellow().bold(),
+                kept.display(),
+                e
+            );
+            eprintln!("Build directory kept at {}", build.display());
+        }
+    }
+}
+
+/// build logs of `package` as `(time, path)`, newest first.
+fn logs_of(package: &str) -> Vec<(String, PathBuf)> {
+    let mut logs: Vec<(String, PathBuf)> = fs::read_dir(dir())
+        .map(|dir| {
+            dir.flatten()
+                .filter_map(|entry| {
+                    let name = entry.file_name().into_string().ok()?;
+                    let (pkg, time) = name.strip_suffix(".log")?.rsplit_once('-')?;
+                    (pkg == package).then(|| (time.to_string(), entry.path()))
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    logs.sort_by(|a, b| b.0.cmp(&a.0));
+    logs
+}
+
+fn prune(package: &str) {
+    for (_, old) in logs_of(package).into_iter().skip(KEEP_LOGS) {
+        let _ = fs::remove_file(old);
+    }
+}
+
+/// `rade log build [package]`: show the newest build log of `package`,
+/// or list the build logs when no package is given.
+pub fn show(package: Option<&str>) {
+    let Some(package) = package else {
+        let mut names: Vec<String> = fs::read_dir(dir())
+            .map(|dir| {
+                dir.flatten()
+                    .filter_map(|e| e.file_name().into_string().ok())
+                    .filter(|n| n.ends_with(".log"))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if names.is_empty() {
+            println!("{} No build logs yet", ">>>".yellow().bold());
+            return;
+        }
+        names.sort();
+        for name in names {
+            println!("{}", dir().join(name).display());
+        }
+        return;
+    };
+    let logs = logs_of(package);
+    let Some((time, newest)) = logs.first() else {
+        eprintln!(
+            "{} {}{}",
+            ">>>".red().bold(),
+            "No build logs for ".bold(),
+            package.bold()
+        );
+        std::process::exit(1);
+    };
+    println!(
+        "{} {} {}",
+        ">>>".green().bold(),
+        format!("Build log of {} from", package).bold(),
+        time
+    );
+    match fs::read_to_string(newest) {
+        Ok(log) => print!("{}", log),
+        Err(e) => {
+            eprintln!("{} Failed to read {}", ">>>".red().bold(), newest.display());
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+    }
+    if logs.len() > 1 {
+        println!("\n{}", "Older logs:".bold());
+        for (_, path) in &logs[1..] {
+            println!("  {}", path.display());
+        }
+    }
+}
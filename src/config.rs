@@ -0,0 +1,77 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{prompt::Assume, Package};
+use colored::*;
+use serde::Deserialize;
+use std::{fs, sync::OnceLock};
+
+/// user settings from `~/.comrade/config.toml`.
+/// Every field is optional; a missing file means the defaults.
+///
+/// ```toml
+/// # answer for [y/n] prompts: "ask", "yes" or "no"
+/// assume = "ask"
+/// # a dependency that is already installed outside rade:
+/// # "prefer-system" uses it, "rade" installs rade's own copy anyway
+/// dependency_policy = "prefer-system"
+///
+/// [self_update]
+/// # where `rade self-update` finds releases; required by it
+/// manifest = "https://example.com/rade/releases.toml"
+/// # ed25519 key the release binaries are signed with, hex encoded; required by it
+/// public_key = "..."
+/// ```
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct Config {
+    pub assume: Assume,
+    pub dependency_policy: DependencyPolicy,
+    pub self_update: SelfUpdate,
+}
+
+/// what to do with a dependency that is found in PATH but was not installed by rade.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "kebab-case")]
+pub enum DependencyPolicy {
+    /// use the system copy and skip the install
+    #[default]
+    PreferSystem,
+    /// always install the package with rade
+    Rade,
+}
+
+/// `[self_update]` of the config file. rade ships no release manifest or key,
+/// so `rade self-update` refuses to run until both are set.
+#[derive(Deserialize, Default)]
+#[serde(default)]
+pub struct SelfUpdate {
+    pub manifest: Option<String>,
+    pub public_key: Option<String>,
+}
+
+static CONFIG: OnceLock<Config> = OnceLock::new();
+
+/// the loaded config. A file that cannot be parsed is reported once and ignored.
+pub fn get() -> &'static Config {
+    CONFIG.get_or_init(|| {
+        let path = Package::rade_home().join("config.toml");
+        let Ok(s) = fs::read_to_string(&path) else {
+            return Config::default();
+        };
+        match toml::from_str(&s) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!(
+                    "{} {} {}",
+                    ">>>".yellow().bold(),
+                    "Ignoring invalid config file".bold(),
+                    path.display()
+                );
+                eprintln!("Error code: {}", e);
+                Config::default()
+            }
+        }
+    })
+}
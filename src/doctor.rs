@@ -0,0 +1,349 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{log, logparser, migrate, versions, which, Package};
+use colored::*;
+use std::{
+    collections::BTreeSet,
+    env,
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// directories every rade home has.
+const LAYOUT: [&str; 5] = ["bin", "log", "log/install", "packagelist", "versions"];
+
+/// commands install scripts commonly need.
+const TOOLS: [&str; 3] = ["git", "sh", "make"];
+
+/// a repair `--fix` may apply without asking: nothing the user made is lost.
+enum Fix {
+    CreateDir(PathBuf),
+    /// write a fresh `log/status`, moving a broken one to `status.bak` first
+    NewStatus,
+    Remove(PathBuf),
+    /// bring an install log up to the current schema
+    Migrate(String, PathBuf),
+    /// point `bin/<exe>` at the copy kept under `versions/`
+    Relink {
+        target: PathBuf,
+        link: PathBuf,
+    },
+}
+
+impl Fix {
+    fn apply(&self) -> Result<(), Box<dyn Error>> {
+        match self {
+            Fix::CreateDir(dir) => fs::create_dir_all(dir)?,
+            Fix::NewStatus => {
+                let status = Package::rade_home().join("log/status");
+                if status.exists() {
+                    fs::rename(&status, status.with_extension("bak"))?;
+                }
+                log::write_status(logparser::install_records().len() as i64)?;
+            }
+            Fix::Remove(path) if path.is_dir() => fs::remove_dir_all(path)?,
+            Fix::Remove(path) => fs::remove_file(path)?,
+            Fix::Migrate(package, file) => migrate::migrate_file(package, file)?,
+            Fix::Relink { target, link } => {
+                // a broken link is still there and would make the new one fail
+                if fs::symlink_metadata(link).is_ok() {
+                    fs::remove_file(link)?;
+                }
+                versions::link_bin(target, link)?
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Problem {
+    /// `false` for problems that only need attention, like an old but readable log
+    error: bool,
+    what: String,
+    suggestion: String,
+    fix: Option<Fix>,
+}
+
+impl Problem {
+    fn error(what: String, suggestion: String) -> Problem {
+        Problem {
+            error: true,
+            what,
+            suggestion,
+            fix: None,
+        }
+    }
+
+    fn warning(what: String, suggestion: String) -> Problem {
+        Problem {
+            error: false,
+            ..Problem::error(what, suggestion)
+        }
+    }
+
+    fn fix(mut self, fix: Fix) -> Problem {
+        self.fix = Some(fix);
+        self
+    }
+}
+
+fn layout(home: &Path) -> Vec<Problem> {
+    if !home.is_dir() {
+        return vec![Problem::error(
+            format!("{} does not exist", home.display()),
+            "install rade with its install script".to_string(),
+        )];
+    }
+    LAYOUT
+        .iter()
+        .map(|dir| home.join(dir))
+        .filter(|dir| !dir.is_dir())
+        .map(|dir| {
+            let suggestion = if dir.ends_with("packagelist") {
+                "run `rade update`".to_string()
+            } else {
+                format!("create {}", dir.display())
+            };
+            let problem = Problem::error(format!("{} is missing", dir.display()), suggestion);
+            // the package list has to be cloned, which `rade update` does
+            if dir.ends_with("packagelist") {
+                problem
+            } else {
+                problem.fix(Fix::CreateDir(dir))
+            }
+        })
+        .collect()
+}
+
+fn status(home: &Path) -> Vec<Problem> {
+    let path = home.join("log/status");
+    match fs::read_to_string(&path) {
+        Err(e) if e.kind() == io::ErrorKind::NotFound => vec![Problem::error(
+            format!("{} is missing", path.display()),
+            "write a new status file".to_string(),
+        )
+        .fix(Fix::NewStatus)],
+        Err(e) => vec![Problem::error(
+            format!("cannot read {}: {}", path.display(), e),
+            "check the permissions of the file".to_string(),
+        )],
+        Ok(s) => match log::parse_status_str(&s) {
+            Ok(_) => Vec::new(),
+            Err(e) => vec![Problem::error(
+                format!("{} cannot be parsed: {}", path.display(), e),
+                "write a new status file; the old one is kept as status.bak".to_string(),
+            )
+            .fix(Fix::NewStatus)],
+        },
+    }
+}
+
+fn install_logs(home: &Path) -> Vec<Problem> {
+    let Ok(dir) = fs::read_dir(home.join("log/install")) else {
+        return Vec::new();
+    };
+    let mut problems = Vec::new();
+    for entry in dir.flatten() {
+        let name = entry.file_name().to_string_lossy().to_string();
+        if migrate::is_old(&name, &entry.path()) {
+            problems.push(
+                Problem::warning(
+                    format!("install log of {} is in an old format", name),
+                    "migrate it to the current format; a copy is kept in log/backup".to_string(),
+                )
+                .fix(Fix::Migrate(name, entry.path())),
+            );
+        } else if let Err(e) = Package::read_install_record(&name) {
+            problems.push(Problem::error(
+                format!("install log of {} cannot be read: {}", name, e),
+                format!("reinstall it with `rade install {}`", name),
+            ));
+        }
+    }
+    problems
+}
+
+fn leftovers(home: &Path) -> Vec<Problem> {
+    // both are only filled while an install is running; failed builds kept
+    // with --keep-build are moved to kept-builds/ and not reported
+    [home.join("build"), Package::rade_packagelist().join("temp")]
+        .into_iter()
+        .filter(|path| fs::read_dir(path).is_ok_and(|mut dir| dir.next().is_some()))
+        .map(|path| {
+            Problem::warning(
+                format!(
+                    "{} is left over from an interrupted install",
+                    path.display()
+                ),
+                format!("remove {}", path.display()),
+            )
+            .fix(Fix::Remove(path))
+        })
+        .collect()
+}
+
+fn bin_and_logs(home: &Path) -> Vec<Problem> {
+    let bin = home.join("bin");
+    let mut problems = Vec::new();
+    let records = logparser::install_records();
+    let executables: BTreeSet<&str> = records.iter().map(|r| r.executable.as_str()).collect();
+
+    if let Ok(dir) = fs::read_dir(&bin) {
+        for entry in dir.flatten() {
+            let name = entry.file_name().to_string_lossy().to_string();
+            // rade itself lives in bin/ without an install log
+            if name == "rade" || name.starts_with("rade.") || executables.contains(name.as_str()) {
+                continue;
+            }
+            let path = entry.path();
+            if path.is_symlink() && !path.exists() {
+                problems.push(
+                    Problem::warning(
+                        format!("{} is a broken link with no install log", path.display()),
+                        format!("remove {}", path.display()),
+                    )
+                    .fix(Fix::Remove(path)),
+                );
+            } else {
+                problems.push(Problem::warning(
+                    format!("{} has no install log", path.display()),
+                    "run `rade log rebuild` to recreate its install log, or remove it".to_string(),
+                ));
+            }
+        }
+    }
+
+    for record in &records {
+        let link = bin.join(&record.executable);
+        if link.exists() {
+            continue;
+        }
+        let kept = home
+            .join("versions")
+            .join(&record.package)
+            .join(&record.version)
+            .join(&record.executable);
+        let state = if link.is_symlink() {
+            "a broken link"
+        } else {
+            "missing"
+        };
+        let problem = Problem::error(
+            format!(
+                "{} is installed but {} is {}",
+                record.package,
+                link.display(),
+                state
+            ),
+            format!(
+                "reinstall it with `rade install {}` or forget it with `rade remove {}`",
+                record.package, record.package
+            ),
+        );
+        problems.push(if kept.is_file() {
+            Problem {
+                suggestion: format!("link {} to {}", link.display(), kept.display()),
+                ..problem
+            }
+            .fix(Fix::Relink { target: kept, link })
+        } else {
+            problem
+        });
+    }
+    problems
+}
+
+fn path_setup(home: &Path) -> Vec<Problem> {
+    let bin = home.join("bin");
+    let bin = fs::canonicalize(&bin).unwrap_or(bin);
+    let on_path = env::var_os("PATH").is_some_and(|path| {
+        env::split_paths(&path).any(|dir| fs::canonicalize(&dir).unwrap_or(dir) == bin)
+    });
+    if on_path {
+        return Vec::new();
+    }
+    vec![Problem::warning(
+        format!("{} is not in PATH", bin.display()),
+        "run `rade env --install` and open a new shell".to_string(),
+    )]
+}
+
+fn tools() -> Vec<Problem> {
+    TOOLS
+        .iter()
+        .filter(|tool| which::find(tool).is_none())
+        .map(|tool| {
+            Problem::warning(
+                format!("{} was not found", tool),
+                format!(
+                    "install {} with your system package manager; packages built from source need it",
+                    tool
+                ),
+            )
+        })
+        .collect()
+}
+
+/// `rade doctor`: look for problems in the rade home and the environment.
+/// With `fix`, the repairs that cannot lose anything are applied.
+pub fn doctor(fix: bool) {
+    let home = Package::rade_home();
+    println!(
+        "{} {}",
+        ">>>".green().bold(),
+        "Checking the rade installation...".bold()
+    );
+    let mut problems = layout(&home);
+    if home.is_dir() {
+        problems.extend(status(&home));
+        problems.extend(install_logs(&home));
+        problems.extend(leftovers(&home));
+        problems.extend(bin_and_logs(&home));
+        problems.extend(path_setup(&home));
+    }
+    problems.extend(tools());
+
+    let mut remaining = 0;
+    let mut errors = 0;
+    let mut fixable = 0;
+    for problem in &problems {
+        let mark = if problem.error {
+            ">>>".red().bold()
+        } else {
+            ">>>".yellow().bold()
+        };
+        println!("{} {}", mark, problem.what);
+        match &problem.fix {
+            Some(f) if fix => match f.apply() {
+                Ok(()) => {
+                    println!("    {} {}", "fixed:".green().bold(), problem.suggestion);
+                    continue;
+                }
+                Err(e) => println!("    {} {}", "could not fix:".red().bold(), e),
+            },
+            Some(_) => fixable += 1,
+            None => {}
+        }
+        println!("    {} {}", "fix:".bold(), problem.suggestion);
+        remaining += 1;
+        if problem.error {
+            errors += 1;
+        }
+    }
+
+    if remaining == 0 {
+        println!("{}", "No problems found!".green().bold());
+        return;
+    }
+    println!("\n{} problem(s) found", remaining);
+    if fixable > 0 {
+        println!("{} of them can be fixed with `rade doctor --fix`", fixable);
+    }
+    if errors > 0 {
+        process::exit(1);
+    }
+}
@@ -2,7 +2,13 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
-use crate::Package;
+use crate::{
+    index,
+    install::PackageInfo,
+    journal::{self, Action},
+    log::{self, Reason},
+    prompt, release, toolchain, Package,
+};
 use colored::*;
 use indicatif::{ProgressBar, ProgressStyle};
 use reqwest::blocking::Client;
@@ -16,38 +22,35 @@ use std::{
 };
 use zip::read::ZipArchive;
 
+/// split `<name>-<target>.radepkg` into the package name and the target.
+pub fn archive_name(path: &Path) -> Option<(String, String)> {
+    let stem = path.file_name()?.to_str()?.strip_suffix(".radepkg")?;
+    let at = release::ARCHES
+        .iter()
+        .filter_map(|(_, arch)| stem.rfind(&format!("-{}-", arch)))
+        .max()?;
+    Some((stem[..at].to_string(), stem[at + 1..].to_string()))
+}
+
 impl Package {
     pub fn download_install(package: &str) -> Result<ZipArchive<BufReader<File>>, Box<dyn Error>> {
         let temp = Package::rade_packagelist().join("temp");
         let output = temp.to_str().unwrap();
-        let (url, download_filename) = if cfg!(target_os = "windows") {
-            (
-                format!(
-                    "https://github.com/rade-package-manager/rade-download-lists/releases/download/{}/{}-x86_64-pc-windows-gnu.radepkg",
-                    package, package
-                ),
-                format!("{}-x86_64-pc-windows-gnu.radepkg", package)
-            )
-        } else if cfg!(target_os = "macos") {
-            (
-                format!(
-                    "https://github.com/rade-package-manager/rade-download-lists/releases/download/{}/{}-aarch64-apple-darwin.radepkg",
-                    package, package
-                ),
-                format!("{}-aarch64-apple-darwin.radepkg", package)
-            )
-        } else if cfg!(target_os = "linux") {
-            (
-                format!(
-                    "https://github.com/rade-package-manager/rade-download-lists/releases/download/{}/{}-x86_64-unknown-linux-gnu.radepkg",
-                    package, package
-                ),
-                format!("{}-x86_64-unknown-linux-gnu.radepkg",package)
+        // the same name `rade pkg build` gives the archive on a machine like this one
+        let target = release::target();
+        let published = index::get(package).map(|entry| entry.targets);
+        if published.is_some_and(|targets| !targets.is_empty() && !targets.contains(&target)) {
+            return Err(format!(
+                "{} has no binary for {}; build it from source with `rade install --build {}`",
+                package, target, package
             )
-        } else {
-            eprintln!("Not support os.");
-            std::process::exit(1);
-        };
+            .into());
+        }
+        let download_filename = format!("{}-{}.radepkg", package, target);
+        let url = format!(
+            "https://github.com/rade-package-manager/rade-download-lists/releases/download/{}/{}",
+            package, download_filename
+        );
 
         println!(
             "{} {} {}",
@@ -94,15 +97,167 @@ impl Package {
         Ok(archive)
     }
 
+    /// download and unpack the package, returning the sha256 of the archive.
+    /// With `expected`, an archive with another sha256 is refused before it is unpacked.
+    /// a failure is written to the journal and ends the process.
+    pub fn download_and_unpack(
+        package: &str,
+        version: &str,
+        expected: Option<&str>,
+    ) -> Result<Option<String>, Box<dyn Error>> {
+        let archive = match Package::download_install(package) {
+            Ok(archive) => archive,
+            Err(e) => {
+                let _ = journal::Entry::new(Action::Install, package)
+                    .after(version)
+                    .failed(&format!("failed to download package: {}", e))
+                    .record();
+                eprintln!("{} Failed to download {}", ">>>".red().bold(), package);
+                eprintln!("Error code: {}", e);
+                return Err(e);
+            }
+        };
+        let temp = Package::rade_packagelist().join("temp");
+        let hash = journal::sha256_file(&temp).ok();
+        if let Some(expected) = expected {
+            if hash.as_deref() != Some(expected) {
+                let _ = fs::remove_file(&temp);
+                let e = format!(
+                    "the archive of {} changed: expected sha256 {}, got {}",
+                    package,
+                    expected,
+                    hash.as_deref().unwrap_or("none")
+                );
+                let _ = journal::Entry::new(Action::Install, package)
+                    .after(version)
+                    .source(hash)
+                    .failed(&e)
+                    .record();
+                eprintln!("{} {}", ">>>".red().bold(), e);
+                return Err(e.into());
+            }
+        }
+        if let Err(e) = Package::unpack_package(archive, package) {
+            let _ = journal::Entry::new(Action::Build, package)
+                .after(version)
+                .source(hash)
+                .failed(&format!("failed to unpack package: {}", e))
+                .record();
+            eprintln!("{} Failed to unpack {}", ">>>".red().bold(), package);
+            eprintln!("Error code: {}", e);
+            return Err(e);
+        }
+        Ok(hash)
+    }
+
+    /// ## install_archive
+    ///
+    /// install a `.radepkg` from the local disk, with the same unpack step as a download.
+    pub fn install_archive(path: &Path) -> Result<(), Box<dyn Error>> {
+        let (name, target) = match archive_name(path) {
+            Some(parts) => parts,
+            None => {
+                eprintln!(
+                    "{} {} is not named <name>-<target>.radepkg",
+                    ">>>".red().bold(),
+                    path.display()
+                );
+                return Err("unexpected archive name".into());
+            }
+        };
+        if target != release::target() {
+            eprintln!(
+                "{} {} is built for {}, but this machine is {}",
+                ">>>".red().bold(),
+                path.display(),
+                target.bold(),
+                release::target().bold()
+            );
+            return Err(format!("archive target {} does not match", target).into());
+        }
+        let mut archive = ZipArchive::new(BufReader::new(File::open(path)?))?;
+        let hash = journal::sha256_file(path)?;
+        let mut exe = String::new();
+        archive.by_name("exec_name")?.read_to_string(&mut exe)?;
+        let exe = exe.trim().to_string();
+        // archives made by `rade pkg build` carry their package.toml
+        let info: Option<PackageInfo> = match archive.by_name("package.toml") {
+            Ok(mut f) => {
+                let mut s = String::new();
+                f.read_to_string(&mut s)?;
+                Some(toml::from_str(&s)?)
+            }
+            Err(_) => Package::read_package_info(&name).ok(),
+        };
+        let (version, dependencies, requires) = match info {
+            Some(info) => (
+                info.version,
+                info.dependencies,
+                info.requires_commands.unwrap_or_default(),
+            ),
+            None => ("unknown".to_string(), Vec::new(), Vec::new()),
+        };
+        toolchain::check_commands(&name, &requires)?;
+        let dependencies: Vec<String> =
+            dependencies.into_iter().filter(|d| !d.is_empty()).collect();
+        let location = fs::canonicalize(path)
+            .map(|p| format!("file://{}", p.display()))
+            .unwrap_or_else(|_| path.display().to_string());
+
+        println!("{} {}", "install package:".bold(), name);
+        println!("{} {}", "executable file name:".bold(), exe);
+        println!("{} {}", "versions:".bold(), version);
+        println!("{} {:?}", "dependencies:".bold(), dependencies);
+        println!("{} {}", "sha256:".bold(), hash);
+        if !prompt::confirm(&format!("\n{} {}?", "install".bold(), name)) {
+            return Ok(());
+        }
+        if !dependencies.is_empty() {
+            println!("Installing Dependencies");
+            for d in &dependencies {
+                Package::install_for_dependence(d, true)?;
+            }
+        }
+        let bin = Package::rade_home().join("bin/").join(&exe);
+        if bin.is_symlink() {
+            fs::remove_file(&bin)?;
+        }
+        if let Err(e) = Package::unpack_package(archive, &name) {
+            let _ = journal::Entry::new(Action::Build, &name)
+                .after(&version)
+                .source(Some(hash))
+                .failed(&format!("failed to unpack package: {}", e))
+                .record();
+            eprintln!("{} Failed to unpack {}", ">>>".red().bold(), name);
+            eprintln!("Error code: {}", e);
+            return Err(e);
+        }
+        Package::record_install(
+            &name,
+            &exe,
+            &location,
+            &version,
+            log::Name::new(&Package::rade_home().join("log/install/"))
+                .dependencies(&dependencies)
+                .reason(Reason::Explicit)
+                .source(Some(hash.clone())),
+            journal::Entry::new(Action::Install, &name).source(Some(hash)),
+            true,
+        );
+        println!("{}", "All done!".bold());
+        Ok(())
+    }
+
     pub fn unpack_package(
         mut archive: ZipArchive<BufReader<File>>,
         package: &str,
     ) -> Result<(), Box<dyn Error>> {
-        let build_dir = Package::rade_home().join("build/");
+        // other packages may be building next to it in build/
+        let build_dir = Package::rade_home().join("build/").join(package);
         if build_dir.exists() {
             fs::remove_dir_all(&build_dir)?;
         }
-        fs::create_dir(&build_dir)?;
+        fs::create_dir_all(&build_dir)?;
 
         let mut exec_name = String::new();
         println!(
@@ -122,8 +277,8 @@ impl Package {
 
             // パスのディレクトリ部分を作成する
             if let Some(p) = outpath.parent() {
-                if !p.exists() {
-                    fs::create_dir_all(p)?;
+                if !build_dir.join(p).exists() {
+                    fs::create_dir_all(build_dir.join(p))?;
                 }
             }
 
@@ -155,19 +310,18 @@ impl Package {
             }
         }
 
-        let home = Package::rade_home();
         let bin = Package::rade_home().join("bin/");
         println!("{} {}", ">>>".yellow().bold(), "Run install.sh".bold());
-        Package::parse_sh(&home.join("build/install.sh"))?;
+        Package::parse_sh(&build_dir.join("install.sh"))?;
 
         if !bin.is_dir() {
             panic!("Error: 'bin' is not a directory");
         }
-        if !home.join("build/").join(&exec_name).exists() {
+        if !build_dir.join(&exec_name).exists() {
             panic!("Error: exec_name file does not exist at the expected path");
         }
         println!("{} {}", ">>>".green().bold(), "move file...".bold());
-        fs::rename(home.join("build/").join(&exec_name), bin.join(&exec_name))?;
+        fs::rename(build_dir.join(&exec_name), bin.join(&exec_name))?;
         println!(
             "{} {}",
             ">>>".green().bold(),
@@ -206,12 +360,8 @@ impl Package {
         Ok(false)
     }
     pub fn download_get_execname(package: &str) -> Result<String, Box<dyn Error>> {
-        let pkg = format!(
-            "{}{}/exec_name",
-            Package::rade_packagelist().display(),
-            package
-        );
-        let f = fs::read_to_string(pkg)?;
-        Ok(f.trim().to_string())
+        index::get(package)
+            .and_then(|entry| entry.exec_name)
+            .ok_or_else(|| format!("{} has no exec_name in the package list", package).into())
     }
 }
@@ -0,0 +1,304 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{index, logparser, Package};
+use clap::ValueEnum;
+use colored::*;
+use std::{
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+const BEGIN: &str = "# >>> rade env >>>";
+const END: &str = "# <<< rade env <<<";
+
+/// shells `rade env` can write for.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Debug)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Posix,
+}
+
+impl Shell {
+    /// the shell in `$SHELL`, or `Posix` if it is not one of ours.
+    fn detect() -> Shell {
+        let shell = env::var("SHELL").unwrap_or_default();
+        match Path::new(&shell).file_name().and_then(|n| n.to_str()) {
+            Some("bash") => Shell::Bash,
+            Some("zsh") => Shell::Zsh,
+            Some("fish") => Shell::Fish,
+            _ => Shell::Posix,
+        }
+    }
+
+    /// the file `--install` writes to.
+    fn rc_file(self) -> PathBuf {
+        let home = dirs::home_dir().expect("Failed to get home dir");
+        match self {
+            Shell::Bash => home.join(".bashrc"),
+            Shell::Zsh => env::var_os("ZDOTDIR")
+                .map(PathBuf::from)
+                .unwrap_or(home)
+                .join(".zshrc"),
+            Shell::Fish => home.join(".config/fish/conf.d/rade.fish"),
+            Shell::Posix => home.join(".profile"),
+        }
+    }
+
+    /// `value` as one literal word: nothing in it is expanded or run by the shell.
+    fn quote(self, value: &str) -> String {
+        match self {
+            Shell::Fish => format!("'{}'", value.replace('\\', "\\\\").replace('\'', "\\'")),
+            _ => format!("'{}'", value.replace('\'', "'\\''")),
+        }
+    }
+
+    fn export(self, name: &str, value: &str) -> String {
+        match self {
+            Shell::Fish => format!("set -gx {} {}", name, self.quote(value)),
+            _ => format!("export {}={}", name, self.quote(value)),
+        }
+    }
+
+    /// put `dir` in front of a `:` separated list such as PATH, once.
+    fn prepend(self, name: &str, dir: &Path) -> String {
+        let dir = dir.display().to_string();
+        match self {
+            Shell::Fish => format!(
+                "contains -- {dir} ${name}; or set -gx {name} {dir} ${name}",
+                dir = self.quote(&dir),
+                name = name
+            ),
+            _ => format!(
+                "case \":${{{name}:-}}:\" in *:{dir}:*) ;; *) export {name}={dir}${{{name}:+:${name}}} ;; esac",
+                dir = self.quote(&dir),
+                name = name
+            ),
+        }
+    }
+}
+
+/// `true` for names a shell accepts as a variable: `[A-Za-z_][A-Za-z0-9_]*`.
+pub fn is_variable_name(name: &str) -> bool {
+    name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_')
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// `value` with a leading `~` or `$HOME` replaced by the home directory.
+/// Nothing else is expanded, since the value is quoted literally.
+fn expand_home(value: &str) -> String {
+    let Some(home) = dirs::home_dir() else {
+        return value.to_string();
+    };
+    let rest = ["$HOME", "${HOME}", "~"]
+        .iter()
+        .find_map(|prefix| value.strip_prefix(prefix))
+        .filter(|rest| rest.is_empty() || rest.starts_with('/'));
+    match rest {
+        Some(rest) => format!("{}{}", home.display(), rest),
+        None => value.to_string(),
+    }
+}
+
+/// the lines that set up the environment for `shell`.
+fn script(shell: Shell) -> Vec<String> {
+    let home = Package::rade_home();
+    let share = home.join("share");
+    let mut lines = vec![
+        shell.prepend("PATH", &home.join("bin")),
+        // the trailing ":" keeps the system man pages
+        match shell {
+            Shell::Fish => format!(
+                "set -q MANPATH; or set -gx MANPATH ''; contains -- {dir} $MANPATH; or set -gx MANPATH {dir} $MANPATH",
+                dir = shell.quote(&share.join("man").display().to_string())
+            ),
+            _ => format!(
+                "case \":${{MANPATH:-}}:\" in *:{dir}:*) ;; *) export MANPATH={dir}:\"${{MANPATH:-}}\" ;; esac",
+                dir = shell.quote(&share.join("man").display().to_string())
+            ),
+        },
+    ];
+    match shell {
+        Shell::Bash => lines.push(shell.export(
+            "BASH_COMPLETION_USER_DIR",
+            &share.join("bash-completion").display().to_string(),
+        )),
+        Shell::Zsh => lines.push(format!(
+            "fpath=({} $fpath)",
+            shell.quote(&share.join("zsh/site-functions").display().to_string())
+        )),
+        Shell::Fish => lines.push(format!(
+            "contains -- {dir} $fish_complete_path; or set -gx fish_complete_path {dir} $fish_complete_path",
+            dir = shell.quote(&share.join("fish/vendor_completions.d").display().to_string())
+        )),
+        Shell::Posix => {}
+    }
+    for record in logparser::install_records() {
+        let Some(entry) = index::get(&record.package) else {
+            continue;
+        };
+        if !entry.env.is_empty() {
+            lines.push(format!("# {}", record.package));
+        }
+        for (name, value) in &entry.env {
+            // the package list is not trusted to name variables safely
+            if !is_variable_name(name) {
+                eprintln!(
+                    "{} Ignoring {} of {}: not a valid variable name",
+                    ">>>".yellow().bold(),
+                    name,
+                    record.package
+                );
+                continue;
+            }
+            lines.push(shell.export(name, &expand_home(value)));
+        }
+    }
+    lines
+}
+
+/// the guarded block `--install` puts in the rc file.
+/// It runs `rade env` at shell start, so packages installed later are picked up.
+fn snippet(shell: Shell, rade: &Path) -> String {
+    let rade = shell.quote(&rade.display().to_string());
+    let shell_name = format!("{:?}", shell).to_lowercase();
+    let body = match shell {
+        Shell::Fish => format!(
+            "if test -x {rade}\n    {rade} env --shell fish | source\nend",
+            rade = rade
+        ),
+        _ => format!(
+            "if [ -x {rade} ]; then\n    eval \"$({rade} env --shell {shell})\"\nfi",
+            rade = rade,
+            shell = shell_name
+        ),
+    };
+    format!("{}\n{}\n{}\n", BEGIN, body, END)
+}
+
+/// add the snippet to `rc`, or replace the one already there.
+fn install(rc: &Path, snippet: &str) -> Result<bool, Box<dyn Error>> {
+    let current = fs::read_to_string(rc).unwrap_or_default();
+    let updated = match (current.find(BEGIN), current.find(END)) {
+        (Some(begin), Some(end)) if begin < end => {
+            let end = end + END.len();
+            let end = if current[end..].starts_with('\n') {
+                end + 1
+            } else {
+                end
+            };
+            format!("{}{}{}", &current[..begin], snippet, &current[end..])
+        }
+        _ if current.is_empty() || current.ends_with('\n') => format!("{}{}", current, snippet),
+        _ => format!("{}\n{}", current, snippet),
+    };
+    if updated == current {
+        return Ok(false);
+    }
+    if let Some(dir) = rc.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(rc, updated)?;
+    Ok(true)
+}
+
+/// `rade env`: print the shell setup, or with `install_rc`, add it to the shell's rc file.
+pub fn env(shell: Option<Shell>, install_rc: bool) {
+    let shell = shell.unwrap_or_else(Shell::detect);
+    if !install_rc {
+        for line in script(shell) {
+            println!("{}", line);
+        }
+        return;
+    }
+
+    let rade = env::current_exe()
+        .and_then(fs::canonicalize)
+        .unwrap_or_else(|_| Package::rade_home().join("bin/rade"));
+    let rc = shell.rc_file();
+    match install(&rc, &snippet(shell, &rade)) {
+        Ok(true) => {
+            println!(
+                "{} {} {}",
+                ">>>".green().bold(),
+                "Added rade to".bold(),
+                rc.display()
+            );
+            println!("Open a new shell to use it.");
+        }
+        Ok(false) => println!(
+            "{} {} is already set up",
+            ">>>".yellow().bold(),
+            rc.display()
+        ),
+        Err(e) => {
+            eprintln!("{} Failed to update {}", ">>>".red().bold(), rc.display());
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn quote_is_literal() {
+        let cases = [
+            (Shell::Posix, "/opt/tools", "'/opt/tools'"),
+            (Shell::Bash, "/home/o'brien/bin", "'/home/o'\\''brien/bin'"),
+            (Shell::Zsh, "$(rm -rf ~)", "'$(rm -rf ~)'"),
+            (Shell::Fish, "/home/o'brien/bin", "'/home/o\\'brien/bin'"),
+            (Shell::Fish, "C:\\tools", "'C:\\\\tools'"),
+        ];
+        for (shell, value, expected) in cases {
+            assert_eq!(shell.quote(value), expected, "{:?} {:?}", shell, value);
+        }
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn quoted_values_round_trip_through_sh() {
+        for value in ["/home/o'brien/my tools", "''", "$HOME `id` \\n \"x\"", ""] {
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!("printf %s {}", Shell::Posix.quote(value)))
+                .output()
+                .unwrap();
+            assert_eq!(String::from_utf8_lossy(&output.stdout), value);
+        }
+    }
+
+    #[test]
+    fn variable_names() {
+        for name in ["PATH", "_x", "GOPATH_2", "a"] {
+            assert!(is_variable_name(name), "{:?}", name);
+        }
+        for name in ["", "2X", "A-B", "A B", "X=1", "$X", "NAME;rm"] {
+            assert!(!is_variable_name(name), "{:?}", name);
+        }
+    }
+
+    #[test]
+    fn expand_home_only_at_the_start() {
+        let home = dirs::home_dir().unwrap().display().to_string();
+        let cases = [
+            ("~", home.clone()),
+            ("~/go", format!("{}/go", home)),
+            ("$HOME/.cargo", format!("{}/.cargo", home)),
+            ("${HOME}/.cargo", format!("{}/.cargo", home)),
+            ("~user/go", "~user/go".to_string()),
+            ("$HOMEDIR/x", "$HOMEDIR/x".to_string()),
+            ("/opt/$HOME", "/opt/$HOME".to_string()),
+        ];
+        for (value, expected) in cases {
+            assert_eq!(expand_home(value), expected, "{:?}", value);
+        }
+    }
+}
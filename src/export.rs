@@ -0,0 +1,273 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    journal::{self, Action},
+    log::{self, Reason},
+    logparser, prompt, Package,
+};
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{collections::HashSet, error::Error, fs, path::Path};
+
+/// the installed package set written by `rade export`.
+#[derive(Serialize, Deserialize)]
+pub struct Export {
+    #[serde(default)]
+    pub package: Vec<Exported>,
+}
+
+/// one installed package.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Exported {
+    pub name: String,
+    pub version: String,
+    /// commit or archive hash it was built from, if it was recorded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+    #[serde(default)]
+    pub reason: Reason,
+}
+
+/// what `rade import` does with one package.
+enum Step {
+    Install(Exported),
+    Use { package: Exported, from: String },
+    Mark(Exported),
+    Unsatisfiable { package: Exported, reason: String },
+}
+
+/// `rade export`: write the installed packages to `file`, or to stdout.
+pub fn export(file: Option<&Path>) {
+    let export = Export {
+        package: logparser::install_records()
+            .into_iter()
+            .map(|r| Exported {
+                name: r.package,
+                version: r.version,
+                source: r.source,
+                reason: r.reason,
+            })
+            .collect(),
+    };
+    let toml_str = match toml::to_string(&export) {
+        Ok(s) => s,
+        Err(e) => {
+            eprintln!("{} Failed to export: {}", ">>>".red().bold(), e);
+            std::process::exit(1);
+        }
+    };
+    match file {
+        Some(file) => {
+            if let Err(e) = fs::write(file, toml_str) {
+                eprintln!(
+                    "{} {} {}",
+                    ">>>".red().bold(),
+                    "Failed to write".bold(),
+                    file.display()
+                );
+                eprintln!("Error code: {}", e);
+                std::process::exit(1);
+            }
+            println!(
+                "{} Exported {} packages to {}",
+                ">>>".green().bold(),
+                export.package.len(),
+                file.display()
+            );
+        }
+        None => print!("{}", toml_str),
+    }
+}
+
+/// dependencies come before the packages that need them, so they are pinned too.
+fn install_order(packages: &[Exported]) -> Vec<Exported> {
+    fn visit(
+        package: &Exported,
+        packages: &[Exported],
+        seen: &mut HashSet<String>,
+        order: &mut Vec<Exported>,
+    ) {
+        if !seen.insert(package.name.clone()) {
+            return;
+        }
+        if let Ok(info) = Package::read_package_info(&package.name) {
+            for d in &info.dependencies {
+                if let Some(dep) = packages.iter().find(|p| p.name == *d) {
+                    visit(dep, packages, seen, order);
+                }
+            }
+        }
+        order.push(package.clone());
+    }
+    let mut seen = HashSet::new();
+    let mut order = Vec::new();
+    for p in packages {
+        visit(p, packages, &mut seen, &mut order);
+    }
+    order
+}
+
+fn plan(export: &Export) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for package in install_order(&export.package) {
+        if let Ok(record) = Package::read_install_record(&package.name) {
+            if record.version == package.version {
+                if record.reason != package.reason {
+                    steps.push(Step::Mark(package));
+                }
+                continue;
+            }
+            let retained = Package::retained_versions(&package.name)
+                .iter()
+                .any(|(v, _)| *v == package.version);
+            if retained {
+                steps.push(Step::Use {
+                    package,
+                    from: record.version,
+                });
+                continue;
+            }
+        }
+        match Package::read_package_info(&package.name) {
+            Ok(info) if info.version == package.version => steps.push(Step::Install(package)),
+            Ok(info) => steps.push(Step::Unsatisfiable {
+                reason: format!("the package list has {}", info.version),
+                package,
+            }),
+            Err(_) => steps.push(Step::Unsatisfiable {
+                package,
+                reason: "not in the package list".to_string(),
+            }),
+        }
+    }
+    steps
+}
+
+fn print_plan(steps: &[Step]) {
+    for step in steps {
+        match step {
+            Step::Install(p) => println!("{} {} {}", "+".green().bold(), p.name.bold(), p.version),
+            Step::Use { package, from } => println!(
+                "{} {} {} → {} (already installed)",
+                "~".cyan().bold(),
+                package.name.bold(),
+                from,
+                package.version
+            ),
+            Step::Mark(p) => println!(
+                "{} {} {} (mark as {})",
+                "~".cyan().bold(),
+                p.name.bold(),
+                p.version,
+                match p.reason {
+                    Reason::Explicit => "explicit",
+                    Reason::Dependency => "dependency",
+                }
+            ),
+            Step::Unsatisfiable { package, reason } => println!(
+                "{} {} {}: {}",
+                "!".red().bold(),
+                package.name.bold(),
+                package.version,
+                reason
+            ),
+        }
+    }
+}
+
+fn apply(step: &Step) -> Result<(), Box<dyn Error>> {
+    let (package, result) = match step {
+        // built packages are checked out at the exported commit, downloaded ones
+        // are refused if the archive no longer has the exported hash
+        Step::Install(p) => (
+            p,
+            Package::install_at(&p.name, true, false, p.source.as_deref()),
+        ),
+        Step::Use { package, from } => {
+            let entry = journal::Entry::new(Action::Use, &package.name)
+                .before(from)
+                .after(&package.version);
+            let result = Package::use_version(&package.name, &package.version);
+            let _ = match &result {
+                Ok(()) => entry.record(),
+                Err(e) => entry.failed(&e.to_string()).record(),
+            };
+            (package, result)
+        }
+        Step::Mark(p) => (p, Ok(())),
+        Step::Unsatisfiable { .. } => return Ok(()),
+    };
+    result?;
+    log::Name::new(&Package::rade_home().join("log/install/"))
+        .set_reason(&package.name, package.reason)
+}
+
+/// `rade import`: install the package set written by `rade export`.
+pub fn import(file: &Path) {
+    let export: Export = match fs::read_to_string(file)
+        .map_err(|e| e.to_string())
+        .and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()))
+    {
+        Ok(e) => e,
+        Err(e) => {
+            eprintln!(
+                "{} {} {}",
+                ">>>".red().bold(),
+                "Failed to read".bold(),
+                file.display()
+            );
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+    };
+
+    let steps = plan(&export);
+    let unsatisfiable = steps
+        .iter()
+        .filter(|s| matches!(s, Step::Unsatisfiable { .. }))
+        .count();
+    if steps.len() == unsatisfiable {
+        print_plan(&steps);
+        if unsatisfiable == 0 {
+            println!("{} Everything is already installed.", ">>>".yellow().bold());
+            return;
+        }
+        std::process::exit(1);
+    }
+    println!("{}", "Planned changes:".bold());
+    print_plan(&steps);
+    if !prompt::confirm(&format!("\nApply {} changes?", steps.len() - unsatisfiable)) {
+        println!("{} Import canceled.", ">>>".cyan().bold());
+        return;
+    }
+
+    let mut failed: Vec<String> = Vec::new();
+    for step in &steps {
+        if let Err(e) = apply(step) {
+            let name = match step {
+                Step::Install(p) | Step::Mark(p) => &p.name,
+                Step::Use { package, .. } | Step::Unsatisfiable { package, .. } => &package.name,
+            };
+            eprintln!(
+                "{} Failed to import {}: {}. Skipping.",
+                ">>>".red().bold(),
+                name,
+                e
+            );
+            failed.push(name.clone());
+        }
+    }
+    println!();
+    println!(
+        "{} {} applied, {} failed, {} unsatisfiable",
+        ">>>".green().bold(),
+        steps.len() - unsatisfiable - failed.len(),
+        failed.len(),
+        unsatisfiable
+    );
+    if !failed.is_empty() || unsatisfiable > 0 {
+        std::process::exit(1);
+    }
+}
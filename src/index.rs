@@ -0,0 +1,268 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{install::PackageInfo, Package};
+use chrono::Utc;
+use colored::*;
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    sync::Mutex,
+    time::SystemTime,
+};
+
+/// bumped when the layout of the index changes, so an old index is rebuilt.
+const FORMAT: u32 = 4;
+
+/// targets binary packages are published for when `package.toml` does not say.
+const DOWNLOAD_TARGETS: [&str; 3] = [
+    "x86_64-unknown-linux-gnu",
+    "aarch64-apple-darwin",
+    "x86_64-pc-windows-gnu",
+];
+
+/// `~/.comrade/index.toml`: every `package.toml` of the package list in one file.
+#[derive(Serialize, Deserialize, Default)]
+pub struct Index {
+    format: u32,
+    generated: String,
+    #[serde(default)]
+    package: BTreeMap<String, Entry>,
+    /// packages whose `package.toml` could not be read
+    #[serde(default)]
+    broken: Vec<String>,
+}
+
+/// one package of the index.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Entry {
+    pub version: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub dependencies: Vec<String>,
+    pub language: String,
+    pub repository: String,
+    pub capacity: i64,
+    pub download: bool,
+    /// targets a binary package exists for; empty for packages built from source
+    pub targets: Vec<String>,
+    /// `exec_name` of downloaded packages
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub exec_name: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub env: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub build_requires: Vec<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub requires_commands: Vec<String>,
+}
+
+impl Entry {
+    /// the entry as it was read from `package.toml`.
+    pub fn info(&self) -> PackageInfo {
+        PackageInfo {
+            dependencies: self.dependencies.clone(),
+            language: self.language.clone(),
+            repository: self.repository.clone(),
+            capacity: self.capacity,
+            version: self.version.clone(),
+            download: self.download,
+            description: self.description.clone(),
+            targets: Some(self.targets.clone()),
+            env: Some(self.env.clone()).filter(|e| !e.is_empty()),
+            build_requires: Some(self.build_requires.clone()).filter(|b| !b.is_empty()),
+            requires_commands: Some(self.requires_commands.clone()).filter(|r| !r.is_empty()),
+        }
+    }
+}
+
+static INDEX: Mutex<Option<Index>> = Mutex::new(None);
+
+/// entries taken from a registry for this run, by package, with the registry name.
+/// They are used in place of the package list without changing it.
+static REGISTRY: Mutex<BTreeMap<String, (String, Entry)>> = Mutex::new(BTreeMap::new());
+
+fn path() -> PathBuf {
+    Package::rade_home().join("index.toml")
+}
+
+/// package directories of the package list with the newest modification time of their files.
+fn package_dirs() -> Vec<(String, Option<SystemTime>)> {
+    let Ok(dir) = fs::read_dir(Package::rade_packagelist()) else {
+        return Vec::new();
+    };
+    dir.flatten()
+        .filter(|entry| entry.path().join("package.toml").is_file())
+        .filter_map(|entry| {
+            let name = entry.file_name().into_string().ok()?;
+            // "" is the directory itself, which changes when files are added or removed
+            let modified = ["package.toml", "exec_name", ""]
+                .iter()
+                .filter_map(|f| entry.path().join(f).metadata().ok()?.modified().ok())
+                .max();
+            Some((name, modified))
+        })
+        .collect()
+}
+
+/// read the `package.toml` (and `exec_name`) in `dir` into an entry.
+fn read_entry(dir: &Path) -> Result<Entry, Box<dyn Error>> {
+    let info = Package::read_package_info_from(dir)?;
+    let targets = match info.targets {
+        Some(targets) => targets,
+        None if info.download => DOWNLOAD_TARGETS.iter().map(|t| t.to_string()).collect(),
+        None => Vec::new(),
+    };
+    let exec_name = fs::read_to_string(dir.join("exec_name"))
+        .ok()
+        .map(|s| s.trim().to_string());
+    Ok(Entry {
+        version: info.version,
+        description: info.description,
+        dependencies: info
+            .dependencies
+            .into_iter()
+            .filter(|d| !d.is_empty())
+            .collect(),
+        language: info.language,
+        repository: info.repository,
+        capacity: info.capacity,
+        download: info.download,
+        targets,
+        exec_name,
+        env: info.env.unwrap_or_default(),
+        build_requires: info.build_requires.unwrap_or_default(),
+        requires_commands: info.requires_commands.unwrap_or_default(),
+    })
+}
+
+/// read the package list into a new index.
+/// Entries that cannot be parsed are reported and left out.
+fn generate() -> Index {
+    let mut package = BTreeMap::new();
+    let mut broken = Vec::new();
+    for (name, _) in package_dirs() {
+        match read_entry(&Package::rade_packagelist().join(&name)) {
+            Ok(entry) => {
+                package.insert(name, entry);
+            }
+            Err(e) => {
+                eprintln!(
+                    "{} Skipping {} in the package list: {}",
+                    ">>>".yellow().bold(),
+                    name,
+                    e
+                );
+                broken.push(name);
+            }
+        }
+    }
+    Index {
+        format: FORMAT,
+        generated: Utc::now().to_rfc3339(),
+        package,
+        broken,
+    }
+}
+
+/// `true` when the index file is older than the package list or lists other packages.
+fn is_stale(index: &Index) -> bool {
+    if index.format != FORMAT {
+        return true;
+    }
+    let Some(written) = path().metadata().ok().and_then(|m| m.modified().ok()) else {
+        return true;
+    };
+    let dirs = package_dirs();
+    dirs.len() != index.package.len() + index.broken.len()
+        || dirs.iter().any(|(name, modified)| {
+            !(index.package.contains_key(name) || index.broken.contains(name))
+                || modified.is_some_and(|m| m > written)
+        })
+}
+
+/// build the index from the package list and write it.
+pub fn rebuild() -> Result<(), Box<dyn Error>> {
+    let index = generate();
+    fs::write(path(), toml::to_string(&index)?)?;
+    *INDEX.lock().unwrap_or_else(|e| e.into_inner()) = Some(index);
+    Ok(())
+}
+
+/// run `f` on the index, reading it first if needed.
+/// A missing or stale index is rebuilt; if it cannot be written, the fresh one is still used.
+fn with<T>(f: impl FnOnce(&Index) -> T) -> T {
+    let mut guard = INDEX.lock().unwrap_or_else(|e| e.into_inner());
+    if guard.is_none() {
+        let loaded: Option<Index> = fs::read_to_string(path())
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok())
+            .filter(|index| !is_stale(index));
+        *guard = Some(match loaded {
+            Some(index) => index,
+            None => {
+                let index = generate();
+                if Package::rade_packagelist().is_dir() {
+                    if let Ok(s) = toml::to_string(&index) {
+                        let _ = fs::write(path(), s);
+                    }
+                }
+                index
+            }
+        });
+    }
+    f(guard.as_ref().expect("index was just loaded"))
+}
+
+/// the index entry of `package`, or its registry entry after `use_registry`.
+pub fn get(package: &str) -> Option<Entry> {
+    if let Some((_, entry)) = REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(package)
+    {
+        return Some(entry.clone());
+    }
+    with(|index| index.package.get(package).cloned())
+}
+
+/// # use_registry
+/// take `package` from the registry cloned to `~/.comrade/registries/<registry>`
+/// for the rest of this run. The package list itself is left alone.
+pub fn use_registry(package: &str, registry: &str) -> Result<Entry, Box<dyn Error>> {
+    let dir = Package::rade_home()
+        .join("registries/")
+        .join(registry)
+        .join(package);
+    let entry = read_entry(&dir)
+        .map_err(|e| format!("{} is not in registry {}: {}", package, registry, e))?;
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .insert(package.to_string(), (registry.to_string(), entry.clone()));
+    Ok(entry)
+}
+
+/// the registry `package` is taken from in this run, `None` for the package list.
+pub fn registry_of(package: &str) -> Option<String> {
+    REGISTRY
+        .lock()
+        .unwrap_or_else(|e| e.into_inner())
+        .get(package)
+        .map(|(registry, _)| registry.clone())
+}
+
+/// every package of the package list, sorted by name.
+pub fn entries() -> Vec<(String, Entry)> {
+    with(|index| {
+        index
+            .package
+            .iter()
+            .map(|(name, entry)| (name.clone(), entry.clone()))
+            .collect()
+    })
+}
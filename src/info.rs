@@ -2,14 +2,31 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
+use colored::*;
 use dirs::home_dir;
+use serde::Serialize;
 use std::path::PathBuf;
 
-use crate::Package;
+use crate::{output, Package};
 
 /// comrade version.
 pub const VERSION: &str = "0.8.4";
 
+#[derive(Serialize)]
+struct Info {
+    name: String,
+    version: String,
+    description: Option<String>,
+    language: String,
+    capacity: i64,
+    dependencies: Vec<String>,
+    repository: String,
+    download: bool,
+    build_requires: Vec<String>,
+    requires_commands: Vec<String>,
+    installed_version: Option<String>,
+}
+
 impl Package {
     /// # rade_home()
     /// return is PathBuf.
@@ -38,3 +55,56 @@ impl Package {
             .join(".comrade/packagelist/")
     }
 }
+
+/// `rade info`: show the package list entry of a package.
+pub fn show(package: &str) {
+    let info = match Package::read_package_info(package) {
+        Ok(info) => info,
+        Err(_) => output::fail("not_found", &format!("Program not found: {}", package)),
+    };
+    let info = Info {
+        name: package.to_string(),
+        version: info.version,
+        description: info.description,
+        language: info.language,
+        capacity: info.capacity,
+        dependencies: info.dependencies,
+        repository: info.repository,
+        download: info.download,
+        build_requires: info.build_requires.unwrap_or_default(),
+        requires_commands: info.requires_commands.unwrap_or_default(),
+        installed_version: Package::installed_version(package),
+    };
+    if output::is_json() {
+        output::print_json(&info);
+        return;
+    }
+    println!("{} {}", "package:".bold(), info.name);
+    println!("{} {}", "versions:".bold(), info.version);
+    if let Some(description) = &info.description {
+        println!("{} {}", "description:".bold(), description);
+    }
+    println!("{} {}", "language:".bold(), info.language);
+    println!("{} {}bytes", "capacity:".bold(), info.capacity);
+    println!("{} {:?}", "dependencies:".bold(), info.dependencies);
+    println!("{} {}", "repository:".bold(), info.repository);
+    println!("{} {}", "binary download:".bold(), info.download);
+    if !info.build_requires.is_empty() {
+        println!(
+            "{} {}",
+            "build requires:".bold(),
+            info.build_requires.join(", ")
+        );
+    }
+    if !info.requires_commands.is_empty() {
+        println!(
+            "{} {}",
+            "requires commands:".bold(),
+            info.requires_commands.join(", ")
+        );
+    }
+    match info.installed_version {
+        Some(v) => println!("{} {}", "installed:".bold(), v.green()),
+        None => println!("{} no", "installed:".bold()),
+    }
+}
@@ -2,28 +2,68 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
-use crate::{install, log, search, Package};
+use crate::{
+    buildlog,
+    config::{self, DependencyPolicy},
+    index, install,
+    journal::{self, Action},
+    log::{self, Reason},
+    prompt, search, toolchain, which, Package,
+};
 use colored::*;
 use dirs::home_dir;
 use git2::Repository;
 use serde::{Deserialize, Serialize};
 use std::{
-    fs::{self, File},
-    io::{self, Read, Write},
-    path::Path,
-    process::{self, Command, ExitStatus},
+    collections::BTreeMap,
+    error::Error,
+    fs,
+    io::Read,
+    path::{Path, PathBuf},
 };
+/// `package.toml` of a package in the package list.
 #[derive(Debug, Deserialize, Serialize)]
-struct PackageInfo {
-    dependencies: Vec<String>,
-    language: String,
-    repository: String,
-    capacity: i64,
-    version: String,
-    download: bool,
+pub struct PackageInfo {
+    pub dependencies: Vec<String>,
+    pub language: String,
+    pub repository: String,
+    pub capacity: i64,
+    pub version: String,
+    pub download: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    /// targets a binary package is published for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub targets: Option<Vec<String>>,
+    /// environment variables the package needs, exported by `rade env`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub env: Option<BTreeMap<String, String>>,
+    /// commands needed to build from source, such as `cargo >= 1.70`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub build_requires: Option<Vec<String>>,
+    /// commands the package needs on the system; rade does not install them
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requires_commands: Option<Vec<String>>,
 }
 
 impl Package {
+    /// ## read_package_info
+    ///
+    /// the package list entry of `program`, from the package index.
+    /// Unlike `get_package_infos`, a missing package is returned as an error.
+    pub fn read_package_info(program: &str) -> Result<PackageInfo, Box<dyn Error>> {
+        match index::get(program) {
+            Some(entry) => Ok(entry.info()),
+            None => Err(format!("{} is not in the package list", program).into()),
+        }
+    }
+    /// ## read_package_info_from
+    ///
+    /// read `package.toml` in `dir`.
+    pub fn read_package_info_from(dir: &Path) -> Result<PackageInfo, Box<dyn Error>> {
+        let s = fs::read_to_string(dir.join("package.toml"))?;
+        Ok(toml::from_str(&s)?)
+    }
     /// ## get_package_infos
     ///
     /// load the package infos.
@@ -37,32 +77,14 @@ impl Package {
     /// (language, capacity, version, dependencies, repository)
     /// ```
     pub fn get_package_infos(program: &str) -> (String, String, String, Vec<String>, String, bool) {
-        let knife_home = home_dir()
-            .expect("Failed to get ~/.comrade/")
-            .join(".comrade/");
-        let basepath = knife_home.join("packagelist/").join(program);
-        let package = format!("{}/package.toml", basepath.display());
-        fn open_and_read_file<P: AsRef<Path>>(path: P, read: &str, get: &str) -> String {
-            match File::open(path) {
-                Ok(mut f) => {
-                    let mut result = String::new();
-                    f.read_to_string(&mut result)
-                        .unwrap_or_else(|_| panic!("failed to read {}", read));
-                    result
-                }
-                Err(e) => {
-                    eprintln!("{} Failed to get {}", ">>>".red().bold(), get);
-                    eprintln!("Please report this issue to the comrade repository.");
-                    eprintln!("Error code: {}", e);
-                    std::process::exit(1);
-                }
+        let package_info = match index::get(program) {
+            Some(entry) => entry.info(),
+            None => {
+                eprintln!("{} Failed to get packageinfo", ">>>".red().bold());
+                eprintln!("{} is not in the package list.", program);
+                std::process::exit(1);
             }
-        }
-
-        let package_info = open_and_read_file(package, "package info", "packageinfo");
-
-        let package_info: PackageInfo =
-            toml::from_str(&package_info).unwrap_or_else(|_| panic!("failed to parse toml"));
+        };
 
         (
             package_info.language,
@@ -73,6 +95,34 @@ impl Package {
             package_info.download,
         )
     }
+    /// ## check_requirements
+    ///
+    /// make sure the commands in `requires_commands` of `program` are installed,
+    /// and with `build` also those in `build_requires`, before anything is downloaded.
+    pub fn check_requirements(
+        program: &str,
+        version: &str,
+        build: bool,
+    ) -> Result<(), Box<dyn Error>> {
+        let (requires, build_requires) = index::get(program)
+            .map(|e| (e.requires_commands, e.build_requires))
+            .unwrap_or_default();
+        let checked = toolchain::check_commands(program, &requires).and_then(|()| {
+            if build {
+                toolchain::check(program, &build_requires)
+            } else {
+                Ok(())
+            }
+        });
+        if let Err(e) = checked {
+            let _ = journal::Entry::new(Action::Install, program)
+                .after(version)
+                .failed(&e)
+                .record();
+            return Err(e.into());
+        }
+        Ok(())
+    }
     /// ## install
     ///
     /// this function is install the package
@@ -83,42 +133,88 @@ impl Package {
     /// install::Package::install(_install_package_name);
     /// ```
     ///
-    pub fn install(program: &str, source: bool, build: bool) {
-        let _ = build;
+    pub fn install(program: &str, source: bool, build: bool) -> Result<(), Box<dyn Error>> {
+        Package::install_at(program, source, build, None)
+    }
+    /// ## install_at
+    ///
+    /// same as `install`, but a package built from source is checked out at `pin` first,
+    /// and a downloaded package must be the archive whose sha256 is `pin`.
+    pub fn install_at(
+        program: &str,
+        source: bool,
+        build: bool,
+        pin: Option<&str>,
+    ) -> Result<(), Box<dyn Error>> {
+        Package::install_with(program, source, build, pin, true)
+    }
+    /// ## install_upgrade
+    ///
+    /// install the newest version of `program` for an upgrade. Unlike `install`, the new
+    /// version is not marked as asked for, so it is dropped like the versions
+    /// before it once newer ones replace it.
+    pub fn install_upgrade(program: &str) -> Result<(), Box<dyn Error>> {
+        Package::install_with(program, true, false, None, false)
+    }
+    fn install_with(
+        program: &str,
+        source: bool,
+        build: bool,
+        pin: Option<&str>,
+        explicit: bool,
+    ) -> Result<(), Box<dyn Error>> {
         let search_ = search::search_program(program);
         let knife_home = home_dir()
             .expect("Failed to get ~/.comrade/")
             .join(".comrade/");
         let (lang, capa, ver, depen, github, download) = Package::get_package_infos(program);
+        // an upgrade keeps the recorded reason, asking for a package makes it explicit
+        let reason = match Package::read_install_record(program) {
+            Ok(record) if source => record.reason,
+            _ => Reason::Explicit,
+        };
         if search_ && !download || download && build {
-            if home_dir()
-                .expect("failed to get home")
-                .join(".comrade/build")
-                .exists()
-            {
+            Package::check_requirements(program, &ver, true)?;
+            // dependencies are built next to it, in build/<dependency>
+            let build_dir = knife_home.join("build").join(program);
+            if build_dir.exists() {
                 println!(
                     "{} {}",
                     ">>>".green().bold(),
                     "removing build directory...".bold()
                 );
-                fs::remove_dir_all(
-                    home_dir()
-                        .expect("Failed to get home")
-                        .join(".comrade/build"),
-                )
-                .expect("Failed to remove build directory");
+                fs::remove_dir_all(&build_dir)?;
             }
 
             println!("{} {}", ">>>".green().bold(), "Clone package...".bold());
-            if Repository::clone(&github, knife_home.join("build")).is_err() {
-                eprintln!("\n{}: Failed to Clone Repository.", "Error".red());
-                eprintln!("Please report this issue to the comrade repository");
-                std::process::exit(1);
-            }
-            let exe =
-                install::get_program_name(knife_home.join("build/").display().to_string(), program);
+            let commit = match Repository::clone(&github, &build_dir) {
+                Ok(repo) => {
+                    if let Some(pin) = pin {
+                        if let Err(e) = checkout(&repo, pin) {
+                            let _ = journal::Entry::new(Action::Install, program)
+                                .after(&ver)
+                                .failed(&format!("failed to check out {}: {}", pin, e))
+                                .record();
+                            fs::remove_dir_all(&build_dir)?;
+                            return Err(format!("commit {} is not available: {}", pin, e).into());
+                        }
+                    }
+                    journal::head_commit(&repo)
+                }
+                Err(e) => {
+                    let _ = journal::Entry::new(Action::Install, program)
+                        .after(&ver)
+                        .failed(&format!("failed to clone repository: {}", e))
+                        .record();
+                    eprintln!("\n{}: Failed to Clone Repository.", "Error".red());
+                    eprintln!("Please report this issue to the comrade repository");
+                    return Err(format!("failed to clone {}: {}", github, e).into());
+                }
+            };
+            let exe = install::get_program_name(build_dir.display().to_string(), program);
             let exeit = knife_home.join("bin/").join(&exe);
-            if exeit.exists() && !source {
+            let other_version = Package::installed_version(program).is_some_and(|v| v != ver);
+            if exeit.exists() && !source && !other_version {
                 println!(
                     "{} {}",
                     ">>>".red().bold(),
@@ -128,8 +224,8 @@ impl Package {
                     "For more information about this program, please visit {}",
                     github
                 );
-                fs::remove_dir_all(knife_home.join("build/")).expect("Failed to remove dir");
-                std::process::exit(1);
+                fs::remove_dir_all(&build_dir)?;
+                return Err(format!("{} is already installed", program).into());
             }
             println!("{} {}", "install package:".bold(), program);
             println!("{} {}", "executable file name:".bold(), exe);
@@ -138,52 +234,44 @@ impl Package {
             println!("{} {}", "versions:".bold(), ver);
             println!("{} {:?}", "dependencies:".bold(), depen);
             println!("{} {}", "repository:".bold(), github);
-            let mut tmp = String::new();
-            let mut ok_ = "yes";
-            if !source {
-                println!("\n{} {}?", "install".bold(), program);
-                print!("[y/n] ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut tmp).unwrap();
-                ok_ = tmp.trim();
-            }
-            if ["y", "yes", ""].contains(&ok_) {
+            if source || prompt::confirm(&format!("\n{} {}?", "install".bold(), program)) {
                 // Start Installation
                 println!("{} {}", ">>>".green().bold(), "Start Installation".bold());
                 // install dependence
                 if !depen.is_empty() {
                     println!("Installing Dependencies");
-                    depen
-                        .into_iter()
-                        .for_each(|d| Package::install_for_dependence(&d, true));
+                    for d in &depen {
+                        if let Err(e) = Package::install_for_dependence(d, true) {
+                            buildlog::discard(program, &build_dir);
+                            return Err(e);
+                        }
+                    }
                 }
                 println!("{} run install.sh (build start)", ">>>".yellow().bold());
 
-                let status_installsh = process::Command::new("sh")
-                    .arg(knife_home.join("build/install.sh"))
-                    .current_dir(knife_home.join("build"))
-                    .status()
-                    .expect("Failed to start install.sh");
-                if !status_installsh.success() {
-                    println!(
-                    "\n{} install.sh failed. Please report this problem to the comrade repository",
-                    ">>>".red().bold()
-                );
-                    std::process::exit(1);
+                if let Err(e) = buildlog::run(program, &build_dir) {
+                    let _ = journal::Entry::new(Action::Build, program)
+                        .after(&ver)
+                        .source(commit)
+                        .failed(&e.to_string())
+                        .record();
+                    buildlog::discard(program, &build_dir);
+                    return Err(e);
                 }
                 println!("{} {}", ">>>".cyan().bold(), "build end".bold());
-                fs::rename(
-                    knife_home.join("build/").join(&exe),
-                    knife_home.join("bin/").join(&exe),
-                )
-                .expect("Failed to move file");
-                fs::remove_dir_all(knife_home.join("build/")).expect("Failed to remove dir");
-                println!("{} {}", ">>>".green().bold(), "Fill in the log...".bold());
-                let _ = log::Name::new(&knife_home.join("log/install/")).create(
+                fs::rename(build_dir.join(&exe), knife_home.join("bin/").join(&exe))?;
+                fs::remove_dir_all(&build_dir)?;
+                Package::record_install(
                     program,
-                    exe.as_str(),
-                    github.to_string(),
-                    ver.to_string(),
+                    &exe,
+                    &github,
+                    &ver,
+                    log::Name::new(&knife_home.join("log/install/"))
+                        .dependencies(&depen)
+                        .reason(reason)
+                        .source(commit.clone()),
+                    journal::Entry::new(Action::Install, program).source(commit),
+                    explicit,
                 );
                 if !source {
                     println!("{}", "All done!".bold());
@@ -197,9 +285,11 @@ impl Package {
         } else if download {
             let pkg = program;
             let (lang, capa, ver, depen, github, _download) = Package::get_package_infos(program);
+            Package::check_requirements(program, &ver, false)?;
             let exe = Package::download_get_execname(pkg).expect("Failed to get exec_name");
             let exeit = knife_home.join("bin/").join(&exe);
-            if exeit.exists() && !source {
+            let other_version = Package::installed_version(program).is_some_and(|v| v != ver);
+            if exeit.exists() && !source && !other_version {
                 println!(
                     "{} {}",
                     ">>>".red().bold(),
@@ -209,7 +299,7 @@ impl Package {
                     "For more information about this program, please visit {}",
                     github
                 );
-                std::process::exit(1);
+                return Err(format!("{} is already installed", program).into());
             }
             println!("{} {}", "install package:".bold(), program);
             println!("{} {}", "executable file name:".bold(), exe);
@@ -218,59 +308,128 @@ impl Package {
             println!("{} {}", "versions:".bold(), ver);
             println!("{} {:?}", "dependencies:".bold(), depen);
             println!("{} {}", "repository:".bold(), github);
-            let mut tmp = String::new();
-            let mut ok_ = "yes";
-            if !source {
-                println!("\n{} {}?", "install".bold(), program);
-                print!("[y/n] ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut tmp).unwrap();
-                ok_ = tmp.trim();
-            }
-            if ["y", "yes", ""].contains(&ok_) {
+            if source || prompt::confirm(&format!("\n{} {}?", "install".bold(), program)) {
                 // install dependence
                 if !depen.is_empty() {
                     println!("Installing Dependencies");
-                    depen
-                        .into_iter()
-                        .for_each(|d| Package::install_for_dependence(&d, true));
+                    for d in &depen {
+                        Package::install_for_dependence(d, true)?;
+                    }
                 }
-                let archive = Package::download_install(program);
-                let _ = Package::unpack_package(archive.unwrap(), program);
-                println!("{} {}", ">>>".green().bold(), "Fill in the log...".bold());
-                let _ = log::Name::new(&knife_home.join("log/install")).create(
+                let hash = Package::download_and_unpack(program, &ver, pin)?;
+                Package::record_install(
                     program,
-                    Package::download_get_execname(pkg)
-                        .expect("Failed to get exec_name")
-                        .as_str(),
-                    github.to_string(),
-                    ver.to_string(),
+                    &exe,
+                    &github,
+                    &ver,
+                    log::Name::new(&knife_home.join("log/install/"))
+                        .dependencies(&depen)
+                        .reason(reason)
+                        .source(hash.clone()),
+                    journal::Entry::new(Action::Install, program).source(hash),
+                    explicit,
                 );
-            } else {
-                return;
             }
         }
+        Ok(())
     }
-    pub fn install_for_dependence(program: &str, build: bool) {
-        let _ = build;
+    /// ## record_install
+    ///
+    /// after `program` was moved into `bin/`: write its install log with `log`, append
+    /// `entry` to the journal and keep the new version under `versions/`.
+    /// Both logs name the registry `program` was taken from, if any.
+    /// The package is installed by then, so failures are reported, not returned.
+    pub fn record_install(
+        program: &str,
+        exe: &str,
+        repository: &str,
+        version: &str,
+        log: log::Name,
+        entry: journal::Entry,
+        explicit: bool,
+    ) {
+        println!("{} {}", ">>>".green().bold(), "Fill in the log...".bold());
+        let registry = index::registry_of(program);
+        let log = log.registry(registry.clone());
+        let mut entry = entry.after(version).registry(registry);
+        if let Some(before) = Package::installed_version(program) {
+            entry = entry.before(&before);
+        }
+        if let Err(e) = log.create(program, exe, repository.to_string(), version.to_string()) {
+            eprintln!(
+                "{} Failed to write the install log of {}: {}",
+                ">>>".yellow().bold(),
+                program,
+                e
+            );
+        }
+        let _ = entry.record();
+        if let Err(e) = Package::retain_version(program, explicit) {
+            eprintln!(
+                "{} Failed to keep a copy of {}: {}",
+                ">>>".yellow().bold(),
+                program,
+                e
+            );
+        }
+    }
+    /// ## system_copy
+    ///
+    /// the copy of `exe` in PATH that rade did not install, if the dependency policy
+    /// says to use it instead of installing `program`.
+    fn system_copy(program: &str, exe: &str) -> Option<PathBuf> {
+        let path = system_command(config::get().dependency_policy, exe)?;
+        println!(
+            "{} {} {} {} {}",
+            ">>>".green().bold(),
+            "Using".bold(),
+            path.display(),
+            "for".bold(),
+            program.bold()
+        );
+        println!(
+            "Set dependency_policy = \"rade\" in ~/.comrade/config.toml to install it with rade instead."
+        );
+        Some(path)
+    }
+    pub fn install_for_dependence(program: &str, build: bool) -> Result<(), Box<dyn Error>> {
+        // already installed by rade, possibly pinned to another commit
+        if Package::installed_version(program).is_some() {
+            return Ok(());
+        }
         let search_ = search::search_program(program);
         let knife_home = home_dir()
             .expect("Failed to get ~/.comrade/")
             .join(".comrade/");
         let (_lang, _capa, ver, depen, github, download) = Package::get_package_infos(program);
+        // look in PATH before fetching anything; the repository may name the executable
+        // differently, which is checked again after cloning
+        let command = index::get(program)
+            .and_then(|entry| entry.exec_name)
+            .unwrap_or_else(|| program.to_string());
+        if Package::system_copy(program, &command).is_some() {
+            return Ok(());
+        }
         if search_ && !download || download && build {
             if knife_home.join("build/").join(program).exists() {
                 fs::remove_dir_all(knife_home.join("build/").join(program)).unwrap();
             }
-            let _ = fs::create_dir_all(knife_home.join("build/").join(program)).unwrap();
+            fs::create_dir_all(knife_home.join("build/").join(program)).unwrap();
 
             println!("{} {}", ">>>".green().bold(), "Clone package...".bold());
-            if let Err(e) = Repository::clone(&github, knife_home.join("build/").join(program)) {
-                eprintln!("\n{}: Failed to Clone Repository.", "Error".red());
-                eprintln!("Please report this issue to the comrade repository");
-                eprintln!("Error code: {}", e);
-                std::process::exit(1);
-            }
+            let commit = match Repository::clone(&github, knife_home.join("build/").join(program)) {
+                Ok(repo) => journal::head_commit(&repo),
+                Err(e) => {
+                    let _ = journal::Entry::new(Action::Install, program)
+                        .after(&ver)
+                        .failed(&format!("failed to clone repository: {}", e))
+                        .record();
+                    eprintln!("\n{}: Failed to Clone Repository.", "Error".red());
+                    eprintln!("Please report this issue to the comrade repository");
+                    eprintln!("Error code: {}", e);
+                    return Err(format!("failed to clone {}: {}", github, e).into());
+                }
+            };
             let exe = install::get_program_name(
                 knife_home
                     .join("build/")
@@ -279,137 +438,108 @@ impl Package {
                     .to_string(),
                 program,
             );
-            let mut _cmd: ExitStatus;
-            if cfg!(target_os = "windows") {
-                _cmd = Command::new("where")
-                    .arg(&exe)
-                    .stderr(process::Stdio::null())
-                    .stdout(process::Stdio::null())
-                    .status()
-                    .expect("Failed to which");
-            } else {
-                _cmd = Command::new("which")
-                    .arg(&exe)
-                    .stderr(process::Stdio::null())
-                    .stdout(process::Stdio::null())
-                    .status()
-                    .expect("Failed to which");
+            if exe != command && Package::system_copy(program, &exe).is_some() {
+                fs::remove_dir_all(knife_home.join("build/").join(program))?;
+                return Ok(());
             }
-            let mut installed = false;
-            if _cmd.success() {
+            // only checked once rade is going to build it itself
+            if let Err(e) = Package::check_requirements(program, &ver, true) {
+                let _ = fs::remove_dir_all(knife_home.join("build/").join(program));
+                return Err(e);
+            }
+            println!("install package: {}", program);
+            println!("dependencies: {:?}", depen);
+            // install dependence
+            if !depen.is_empty() && !depen[0].is_empty() {
                 println!(
                     "{} {}",
-                    ">>>".red().bold(),
-                    "The program is already installed!".bold()
-                );
-                fs::remove_dir_all(knife_home.join("build/").join(program))
-                    .expect("Failed to remove dir");
-                installed = true
-            }
-            if !installed {
-                println!("install package: {}", program);
-                println!("dependencies: {:?}", depen);
-                // install dependence
-                if !depen.is_empty() && depen[0] != "" {
-                    println!(
-                        "{} {}",
-                        ">>>".green().bold(),
-                        "Install Dependencies...".bold()
-                    );
-                    let _ = depen
-                        .into_iter()
-                        .for_each(|d| Package::install_for_dependence(&d, true));
-                }
-                println!("{} run install.sh (build start)", ">>>".yellow().bold());
-                let progmr = format!(
-                    "{}{}",
-                    knife_home.join("build/").join(program).display(),
-                    "/install.sh"
-                );
-                let status_installsh = process::Command::new("sh")
-                    .arg(progmr)
-                    .current_dir(knife_home.join("build").join(program))
-                    .status()
-                    .expect("Failed to start install.sh");
-                if !status_installsh.success() {
-                    println!(
-                    "\n{} install.sh failed. Please report this problem to the comrade repository",
-                    ">>>".red().bold()
+                    ">>>".green().bold(),
+                    "Install Dependencies...".bold()
                 );
-                    std::process::exit(1);
+                for d in &depen {
+                    if let Err(e) = Package::install_for_dependence(d, true) {
+                        buildlog::discard(program, &knife_home.join("build").join(program));
+                        return Err(e);
+                    }
                 }
-                println!("{} {}", ">>>".cyan().bold(), "build end".bold());
-                fs::rename(
-                    knife_home.join("build/").join(program).join(&exe),
-                    knife_home.join("bin/").join(&exe),
-                )
-                .expect("Failed to move file");
-                fs::remove_dir_all(knife_home.join("build/")).expect("Failed to remove dir");
-                println!("{} {}", ">>>".green().bold(), "Fill in the log...".bold());
-                let _ = log::Name::new(&knife_home.join("log/install/")).create(
-                    program,
-                    exe.as_str(),
-                    github.to_string(),
-                    ver.to_string(),
-                );
-            } else {
-                return;
             }
+            println!("{} run install.sh (build start)", ">>>".yellow().bold());
+            let build_dir = knife_home.join("build").join(program);
+            if let Err(e) = buildlog::run(program, &build_dir) {
+                let _ = journal::Entry::new(Action::Build, program)
+                    .after(&ver)
+                    .source(commit)
+                    .failed(&e.to_string())
+                    .record();
+                buildlog::discard(program, &build_dir);
+                return Err(e);
+            }
+            println!("{} {}", ">>>".cyan().bold(), "build end".bold());
+            fs::rename(
+                knife_home.join("build/").join(program).join(&exe),
+                knife_home.join("bin/").join(&exe),
+            )?;
+            fs::remove_dir_all(knife_home.join("build/").join(program))
+                .expect("Failed to remove dir");
+            Package::record_install(
+                program,
+                &exe,
+                &github,
+                &ver,
+                log::Name::new(&knife_home.join("log/install/"))
+                    .dependencies(&depen)
+                    .reason(Reason::Dependency)
+                    .source(commit.clone()),
+                journal::Entry::new(Action::Install, program).source(commit),
+                false,
+            );
         } else if download {
             let pkg = program;
             let (_lang, _capa, ver, depen, github, _download) = Package::get_package_infos(program);
             let exe = Package::download_get_execname(pkg).expect("Failed to get exec_name");
-            let _exeit = knife_home.join("bin/").join(&exe);
-            let mut _cmd = Command::new("").status().unwrap();
-            if cfg!(target_os = "windows") {
-                _cmd = Command::new("where")
-                    .arg(&exe)
-                    .stderr(process::Stdio::null())
-                    .stdout(process::Stdio::null())
-                    .status()
-                    .expect("Failed to execute where");
-            } else {
-                _cmd = Command::new("which")
-                    .arg(&exe)
-                    .stderr(process::Stdio::null())
-                    .stdout(process::Stdio::null())
-                    .status()
-                    .expect("Failed to execute which");
-            }
-
-            if _cmd.success() {
-                println!(
-                    "{} {}",
-                    ">>>".red().bold(),
-                    "The program is already installed!".bold()
-                );
-            }
-            println!("{} {}", "install package:", program);
-            println!("{} {:?}", "dependencies:", depen);
+            Package::check_requirements(program, &ver, false)?;
+            println!("install package: {}", program);
+            println!("dependencies: {:?}", depen);
             // install dependence
             if !depen.is_empty() {
                 println!("Installing Dependencies");
-                depen
-                    .into_iter()
-                    .for_each(|d| Package::install_for_dependence(&d, true));
+                for d in &depen {
+                    Package::install_for_dependence(d, true)?;
+                }
             }
-            let archive = Package::download_install(program);
-            let _ = Package::unpack_package(archive.unwrap(), program);
-            println!("{} {}", ">>>".green().bold(), "Fill in the log...".bold());
-            let _ = log::Name::new(&knife_home.join("log/install")).create(
+            let hash = Package::download_and_unpack(program, &ver, None)?;
+            Package::record_install(
                 program,
-                Package::download_get_execname(pkg)
-                    .expect("Failed to get exec_name")
-                    .as_str(),
-                github.to_string(),
-                ver.to_string(),
+                &exe,
+                &github,
+                &ver,
+                log::Name::new(&knife_home.join("log/install/"))
+                    .dependencies(&depen)
+                    .reason(Reason::Dependency)
+                    .source(hash.clone()),
+                journal::Entry::new(Action::Install, program).source(hash),
+                false,
             );
-        } else {
-            return;
         }
+        Ok(())
     }
 }
 
+/// the copy of `command` in PATH that rade did not install, unless `policy` is to always use rade.
+fn system_command(policy: DependencyPolicy, command: &str) -> Option<PathBuf> {
+    if policy == DependencyPolicy::Rade {
+        return None;
+    }
+    which::find_system(command)
+}
+
+/// check out `commit` in a freshly cloned repository.
+pub fn checkout(repo: &Repository, commit: &str) -> Result<(), git2::Error> {
+    let object = repo.revparse_single(commit)?;
+    repo.checkout_tree(&object, Some(git2::build::CheckoutBuilder::new().force()))?;
+    repo.set_head_detached(object.id())
+}
+
 pub fn get_program_name(build_dir: String, program: &str) -> String {
     // build_dir
     let exe_name = Path::new(&build_dir).join(".comrade/exe_name");
@@ -419,10 +549,43 @@ pub fn get_program_name(build_dir: String, program: &str) -> String {
     let mut str = String::new();
     if let Ok(mut fl) = fs::File::open(&exe_name) {
         fl.read_to_string(&mut str).expect("failed to read file");
-        return str.trim().to_string();
+        str.trim().to_string()
     } else {
         eprintln!("failed to read file: {}", exe_name.display());
         eprintln!("Please report this issue to the comrade repository");
         std::process::exit(1);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    #[test]
+    fn system_command_follows_the_policy() {
+        let dir = env::temp_dir().join(format!("rade-system-command-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        let tool = dir.join("tool");
+        fs::write(&tool, "#!/bin/sh\n").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(&tool, fs::Permissions::from_mode(0o755)).unwrap();
+        }
+        let command = tool.display().to_string();
+
+        assert_eq!(
+            system_command(DependencyPolicy::PreferSystem, &command),
+            Some(tool.clone())
+        );
+        assert_eq!(system_command(DependencyPolicy::Rade, &command), None);
+        let missing = dir.join("missing").display().to_string();
+        assert_eq!(
+            system_command(DependencyPolicy::PreferSystem, &missing),
+            None
+        );
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::Package;
+use chrono::{Datelike, Utc};
+use git2::Repository;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::{
+    error::Error,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    path::Path,
+};
+
+/// What happened to the package.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Action {
+    Install,
+    Remove,
+    Upgrade,
+    Build,
+    Update,
+    Rollback,
+    Use,
+}
+
+/// How it ended.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Outcome {
+    Success,
+    Failed,
+}
+
+/// One record of the journal.
+/// The journal lives in `log/journal` and is only ever appended to.
+#[derive(Serialize, Deserialize, Debug, Clone)]
+pub struct Entry {
+    pub utc_day: String,
+    pub utc_time: String,
+    pub action: Action,
+    pub package: String,
+    pub version_before: Option<String>,
+    pub version_after: Option<String>,
+    pub source: Option<String>,
+    /// the `Radefile.toml` registry the package came from, if not the package list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub registry: Option<String>,
+    pub outcome: Outcome,
+    pub detail: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct Journal {
+    #[serde(default)]
+    entry: Vec<Entry>,
+}
+
+/// every entry of the journal, oldest first.
+pub fn entries() -> Result<Vec<Entry>, Box<dyn Error>> {
+    let s = fs::read_to_string(Package::rade_home().join("log/journal"))?;
+    let journal: Journal = toml::from_str(&s)?;
+    Ok(journal.entry)
+}
+
+impl Entry {
+    /// start a new entry. The outcome is success until `failed` is called.
+    pub fn new(action: Action, package: &str) -> Self {
+        let utc = Utc::now();
+        Entry {
+            utc_day: format!("{}-{}-{}", utc.year(), utc.month(), utc.day()),
+            utc_time: utc.time().format("%H:%M:%S").to_string(),
+            action,
+            package: package.to_string(),
+            version_before: None,
+            version_after: None,
+            source: None,
+            registry: None,
+            outcome: Outcome::Success,
+            detail: None,
+        }
+    }
+    /// version installed before this action.
+    pub fn before(mut self, version: &str) -> Self {
+        self.version_before = Some(version.to_string());
+        self
+    }
+    /// version installed after this action.
+    pub fn after(mut self, version: &str) -> Self {
+        self.version_after = Some(version.to_string());
+        self
+    }
+    /// source commit or archive hash.
+    pub fn source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+    /// registry the package came from.
+    pub fn registry(mut self, registry: Option<String>) -> Self {
+        self.registry = registry;
+        self
+    }
+    /// mark the entry as failed with a short reason.
+    pub fn failed(mut self, detail: &str) -> Self {
+        self.outcome = Outcome::Failed;
+        self.detail = Some(detail.to_string());
+        self
+    }
+    /// append the entry to `log/journal`.
+    pub fn record(self) -> Result<(), Box<dyn Error>> {
+        let journal = Journal { entry: vec![self] };
+        let toml_str = toml::to_string(&journal)?;
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(Package::rade_home().join("log/journal"))?;
+        writeln!(file, "{}", toml_str)?;
+        Ok(())
+    }
+}
+
+/// commit id checked out in `repo`.
+pub fn head_commit(repo: &Repository) -> Option<String> {
+    let commit = repo.head().ok()?.peel_to_commit().ok()?;
+    Some(commit.id().to_string())
+}
+
+/// sha256 of the file, hex encoded.
+pub fn sha256_file(path: &Path) -> io::Result<String> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = Sha256::new();
+    io::copy(&mut file, &mut hasher)?;
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
@@ -2,68 +2,278 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
+use crate::{index, log::Reason, logparser, output, upgrade, version, Package};
+use chrono::NaiveDateTime;
+use clap::ValueEnum;
 use colored::*;
-use std::fs;
+use serde::Serialize;
+use std::{fs, path::Path};
 
-pub fn list(dir_path: &str, is_show_file: bool) {
-    let dir_path = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join(format!(".comrade/{}", dir_path));
+#[derive(Serialize)]
+struct ListEntry {
+    name: String,
+    version: Option<String>,
+}
 
-    let dir = match fs::read_dir(&dir_path) {
-        Ok(dir) => dir,
-        Err(_) => {
-            eprintln!(
-                "{}{}{}{}{}",
-                "--Error--\n".red().bold(),
-                "Failed to retrieve package list.\n".red().bold(),
-                "please run ".red().bold(),
-                "rade update ".cyan(),
-                "to retrieve package list".red().bold()
-            );
-            std::process::exit(1);
+#[derive(Serialize)]
+struct List {
+    packages: Vec<ListEntry>,
+}
+
+#[derive(Serialize)]
+struct InstalledEntry {
+    name: String,
+    version: String,
+    versions: Vec<String>,
+    executable: String,
+    installed_at: String,
+    repository: String,
+    size: u64,
+    available: Option<String>,
+    outdated: bool,
+    reason: Reason,
+    /// the version was guessed by `rade log rebuild`
+    inferred: bool,
+}
+
+#[derive(Serialize)]
+struct Installed {
+    packages: Vec<InstalledEntry>,
+}
+
+/// sort order of `rade list --installed`.
+#[derive(ValueEnum, Clone, Copy, Default)]
+pub enum SortKey {
+    #[default]
+    Name,
+    Version,
+    Date,
+    Size,
+}
+
+/// `rade list`: every package of the package list, from the package index.
+pub fn list() {
+    if !Package::rade_packagelist().is_dir() {
+        output::fail(
+            "packagelist_missing",
+            "Failed to retrieve package list. Please run `rade update` to retrieve package list",
+        );
+    }
+    let packages = index::entries();
+
+    if output::is_json() {
+        let packages = packages
+            .into_iter()
+            .map(|(name, entry)| ListEntry {
+                name,
+                version: Some(entry.version),
+            })
+            .collect();
+        output::print_json(&List { packages });
+        return;
+    }
+    for (package, _) in packages {
+        println!("{}", package);
+    }
+}
+
+/// installed packages, built from the install records in `log/install/`.
+/// `filter` keeps the packages whose name matches (`*` and `?` are wildcards, otherwise a substring);
+/// with `highlight_outdated`, packages with a newer version in the package list are highlighted.
+pub fn installed(sort: SortKey, filter: Option<String>, highlight_outdated: bool) {
+    if let Err(e) = fs::read_dir(Package::rade_home().join("log/install")) {
+        output::fail(
+            "log_unreadable",
+            &format!("Failed to read log files: {}", e),
+        );
+    }
+    let home = Package::rade_home();
+    let mut installed: Vec<InstalledEntry> = logparser::install_records()
+        .into_iter()
+        .filter(|r| filter.as_ref().is_none_or(|f| matches(f, &r.package)))
+        .map(|r| {
+            let mut versions: Vec<String> = Package::retained_versions(&r.package)
+                .into_iter()
+                .map(|(v, _)| v)
+                .collect();
+            if !versions.contains(&r.version) {
+                versions.insert(0, r.version.clone());
+            }
+            let kept = home.join("versions/").join(&r.package);
+            let size = if kept.is_dir() {
+                dir_size(&kept)
+            } else {
+                fs::metadata(home.join("bin/").join(&r.executable))
+                    .map(|m| m.len())
+                    .unwrap_or_default()
+            };
+            let available = upgrade::available_version(&r.package);
+            InstalledEntry {
+                outdated: available
+                    .as_ref()
+                    .is_some_and(|a| version::is_newer(a, &r.version)),
+                available,
+                name: r.package,
+                version: r.version,
+                versions,
+                executable: r.executable,
+                installed_at: format!("{} {}", r.utc_day, r.utc_time),
+                reason: r.reason,
+                inferred: r.inferred,
+                repository: r.repository,
+                size,
+            }
+        })
+        .collect();
+    match sort {
+        SortKey::Name => {}
+        SortKey::Version => installed.sort_by(|a, b| version::compare(&a.version, &b.version)),
+        SortKey::Date => installed.sort_by_key(|p| installed_at(&p.installed_at)),
+        SortKey::Size => installed.sort_by_key(|p| std::cmp::Reverse(p.size)),
+    }
+
+    if output::is_json() {
+        output::print_json(&Installed {
+            packages: installed,
+        });
+        return;
+    }
+    let rows: Vec<[String; 6]> = installed
+        .iter()
+        .map(|p| {
+            let others: Vec<&str> = p
+                .versions
+                .iter()
+                .filter(|v| **v != p.version)
+                .map(|v| v.as_str())
+                .collect();
+            let mut version = p.version.clone();
+            if p.inferred {
+                version = format!("{} (inferred)", version);
+            }
+            if highlight_outdated && p.outdated {
+                version = format!("{} → {}", version, p.available.as_deref().unwrap_or(""));
+            }
+            if !others.is_empty() {
+                version = format!("{} (also {})", version, others.join(", "));
+            }
+            [
+                p.name.clone(),
+                version,
+                p.executable.clone(),
+                p.installed_at.clone(),
+                format!("{}bytes", p.size),
+                p.repository.clone(),
+            ]
+        })
+        .collect();
+    let header = [
+        "NAME",
+        "VERSION",
+        "EXECUTABLE",
+        "INSTALLED",
+        "SIZE",
+        "REPOSITORY",
+    ];
+    let mut widths = header.map(|h| h.chars().count());
+    for row in &rows {
+        for (w, cell) in widths.iter_mut().zip(row.iter()) {
+            *w = (*w).max(cell.chars().count());
         }
+    }
+    let line = |cells: [&str; 6]| -> String {
+        cells
+            .iter()
+            .zip(widths.iter())
+            .map(|(c, w)| format!("{:<width$}", c, width = w))
+            .collect::<Vec<String>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
     };
+    println!("{}", line(header).bold());
+    for (row, p) in rows.iter().zip(installed.iter()) {
+        let text = line([&row[0], &row[1], &row[2], &row[3], &row[4], &row[5]]);
+        if highlight_outdated && p.outdated {
+            println!("{}", text.yellow().bold());
+        } else {
+            println!("{}", text);
+        }
+    }
+}
 
-    for entry in dir {
-        let entry = entry.expect("Failed to get entry");
-        if entry.path().is_dir() || is_show_file {
-            let path = entry.path().display().to_string();
-            let path: Vec<&str> = path.split("/").collect();
-            let package = path.last().expect("Failed to get package");
-            println!("{}", package);
+/// `pattern` with `*` / `?` wildcards, or a substring when it has none.
+fn matches(pattern: &str, name: &str) -> bool {
+    if !pattern.contains(['*', '?']) {
+        return name.contains(pattern);
+    }
+    fn glob(p: &[char], n: &[char]) -> bool {
+        match (p.first(), n.first()) {
+            (None, None) => true,
+            (Some('*'), _) => glob(&p[1..], n) || (!n.is_empty() && glob(p, &n[1..])),
+            (Some('?'), Some(_)) => glob(&p[1..], &n[1..]),
+            (Some(a), Some(b)) if a == b => glob(&p[1..], &n[1..]),
+            _ => false,
         }
     }
+    let p: Vec<char> = pattern.chars().collect();
+    let n: Vec<char> = name.chars().collect();
+    glob(&p, &n)
 }
 
-#[cfg(target_os = "windows")]
-pub fn list(dir_path: &str, is_show_file: bool) {
-    let dir_path = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join(format!(".comrade/{}", dir_path));
+/// `utc_day utc_time` of an install record; days and months are not zero padded.
+fn installed_at(s: &str) -> Option<NaiveDateTime> {
+    NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").ok()
+}
 
-    let dir = match fs::read_dir(&dir_path) {
-        Ok(dir) => dir,
-        Err(_) => {
-            eprintln!(
-                "{}{}{}{}{}",
-                "--Error--\n".red().bold(),
-                "Failed to retrieve package list.\n".red().bold(),
-                "please run ".red().bold(),
-                "rade update ".cyan(),
-                "to retrieve package list".red().bold()
-            );
-            std::process::exit(1);
-        }
-    };
+/// total size of the files below `path`.
+fn dir_size(path: &Path) -> u64 {
+    fs::read_dir(path)
+        .map(|dir| {
+            dir.flatten()
+                .map(|entry| match entry.metadata() {
+                    Ok(m) if m.is_dir() => dir_size(&entry.path()),
+                    Ok(m) => m.len(),
+                    Err(_) => 0,
+                })
+                .sum()
+        })
+        .unwrap_or_default()
+}
 
-    for entry in dir {
-        let entry = entry.expect("Failed to get entry");
-        if entry.path().is_dir() || is_show_file {
-            let path = entry.path().display().to_string();
-            let path: Vec<&str> = path.split("¥").collect();
-            let package = path.last().expect("Failed to get package");
-            println!("{}", package);
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matches_globs_and_substrings() {
+        let cases = [
+            // no wildcard: a substring
+            ("ell", "hello", true),
+            ("hello", "hello", true),
+            ("world", "hello", false),
+            ("", "hello", true),
+            // `*` is any run of characters, `?` exactly one
+            ("h*", "hello", true),
+            ("*o", "hello", true),
+            ("*ll*", "hello", true),
+            ("h*x", "hello", false),
+            ("*", "", true),
+            ("h?llo", "hello", true),
+            ("h?llo", "hllo", false),
+            ("?", "ab", false),
+            ("rade-*-?", "rade-tool-x", true),
+            // a glob matches the whole name
+            ("ell*", "hello", false),
+        ];
+        for (pattern, name, expected) in cases {
+            assert_eq!(
+                matches(pattern, name),
+                expected,
+                "matches({:?}, {:?})",
+                pattern,
+                name
+            );
         }
     }
 }
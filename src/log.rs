@@ -4,14 +4,18 @@
 
 use chrono::{Datelike, Utc};
 use colored::Colorize;
-use dirs::home_dir;
 use serde::{Deserialize, Serialize};
 use std::error::Error;
 use std::fs;
-use std::io::{self, Write};
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use crate::Package;
+use crate::{
+    index,
+    journal::{self, Action, Outcome},
+    logparser, migrate,
+    migrate::SCHEMA,
+    output, prompt, Package,
+};
 
 #[derive(Serialize, Deserialize)]
 struct Time {
@@ -21,35 +25,106 @@ struct Time {
 #[derive(Serialize, Deserialize)]
 struct Install {
     install_name: String,
+    reason: Reason,
+}
+
+/// why a package is installed.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum Reason {
+    /// the user asked for it
+    #[default]
+    Explicit,
+    /// pulled in by another package
+    Dependency,
 }
 #[derive(Serialize, Deserialize)]
 struct Info {
     version: String,
     repositry: String,
+    dependencies: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    source: Option<String>,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    inferred: bool,
+    /// the `Radefile.toml` registry the package came from, instead of the package list
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    registry: Option<String>,
 }
 #[derive(Serialize, Deserialize)]
 struct Config {
+    schema: i64,
     time: Time,
     install: Install,
     info: Info,
 }
 #[derive(Serialize, Deserialize)]
 struct ConfG {
+    /// missing in status files written before the log schema was recorded
+    #[serde(default)]
+    schema: i64,
     time: Time,
     install: i64,
 }
 
 pub struct Name<'a> {
     pub basedir: &'a Path,
+    dependencies: Vec<String>,
+    reason: Reason,
+    source: Option<String>,
+    inferred: bool,
+    registry: Option<String>,
 }
 
 impl<'a> Name<'a> {
     /// set path.
     pub fn new(base: &'a Path) -> Self {
-        Name { basedir: base }
+        Name {
+            basedir: base,
+            dependencies: Vec::new(),
+            reason: Reason::Explicit,
+            source: None,
+            inferred: false,
+            registry: None,
+        }
+    }
+
+    /// commit or archive hash the package was built from.
+    pub fn source(mut self, source: Option<String>) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// registry the package was installed from, `None` for the package list.
+    pub fn registry(mut self, registry: Option<String>) -> Self {
+        self.registry = registry;
+        self
+    }
+
+    /// the record was reconstructed and its version is a guess.
+    pub fn inferred(mut self, inferred: bool) -> Self {
+        self.inferred = inferred;
+        self
+    }
+
+    /// why the package is installed.
+    pub fn reason(mut self, reason: Reason) -> Self {
+        self.reason = reason;
+        self
+    }
+
+    /// packages the installed package depends on.
+    pub fn dependencies(mut self, dependencies: &[String]) -> Self {
+        self.dependencies = dependencies
+            .iter()
+            .filter(|d| !d.is_empty())
+            .cloned()
+            .collect();
+        self
     }
 
     /// create package log files.
+    /// The install log is written first, so a missing `log/status` cannot lose it.
     pub fn create(
         &self,
         package: &str,
@@ -57,30 +132,65 @@ impl<'a> Name<'a> {
         url: String,
         versi: String,
     ) -> Result<(), Box<dyn Error>> {
-        let _name = self.basedir.join(package);
+        self.write(package, _install, url, versi)?;
+        let status = Package::rade_home().join("log/status");
+        let tml: Option<ConfG> = fs::read_to_string(&status)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok());
+        match tml {
+            Some(mut tml) => {
+                tml.install += 1;
+                fs::write(status, toml::to_string(&tml)?)?;
+            }
+            None => eprintln!(
+                "{} {} is missing or broken; run `rade log rebuild` to recreate it",
+                ">>>".yellow().bold(),
+                status.display()
+            ),
+        }
+        Ok(())
+    }
+    /// write the install log of a package without counting an installation.
+    pub fn write(
+        &self,
+        package: &str,
+        install: &str,
+        url: String,
+        version: String,
+    ) -> Result<(), Box<dyn Error>> {
         let utc = Utc::now();
-        let data = format!("{}-{}-{}", utc.year(), utc.month(), utc.day());
-        let time_ = utc.time().format("%H:%M:%S").to_string();
         let config = Config {
+            schema: SCHEMA,
             time: Time {
-                utc_day: data,
-                utc_time: time_,
+                utc_day: format!("{}-{}-{}", utc.year(), utc.month(), utc.day()),
+                utc_time: utc.time().format("%H:%M:%S").to_string(),
             },
             install: Install {
-                install_name: _install.to_string(),
+                install_name: install.to_string(),
+                reason: self.reason,
             },
             info: Info {
-                version: versi,
+                version,
                 repositry: url,
+                dependencies: self.dependencies.clone(),
+                source: self.source.clone(),
+                inferred: self.inferred,
+                registry: self.registry.clone(),
             },
         };
-        let toml_str = toml::to_string(&config)?;
-        let s = fs::read_to_string(Package::rade_home().join("log/status"))?;
-        let mut tml: ConfG = toml::from_str(&s)?;
-        tml.install += 1;
-        fs::write(&_name, toml_str)?;
-        let update_str = toml::to_string(&tml)?;
-        fs::write(Package::rade_home().join("log/status"), update_str)?;
+        fs::write(self.basedir.join(package), toml::to_string(&config)?)?;
+        Ok(())
+    }
+    /// change the install reason recorded for a package.
+    pub fn set_reason(&self, pkgname: &str, reason: Reason) -> Result<(), Box<dyn Error>> {
+        let path = self.basedir.join(pkgname);
+        let mut log: toml::Table = toml::from_str(&fs::read_to_string(&path)?)?;
+        let install = log
+            .get_mut("install")
+            .and_then(|i| i.as_table_mut())
+            .ok_or("install log has no [install] table")?;
+        install.insert("reason".to_string(), toml::Value::try_from(reason)?);
+        fs::write(path, toml::to_string(&log)?)?;
         Ok(())
     }
     pub fn remove_program(&self, pkgname: &str) {
@@ -89,65 +199,95 @@ impl<'a> Name<'a> {
     }
 }
 
+/// `rade mark`: change why a package is considered installed.
+pub fn mark(package: &str, reason: Reason) {
+    if Package::installed_version(package).is_none() {
+        eprintln!(
+            "{} {}{}",
+            ">>>".red().bold(),
+            "Package is not installed: ".bold(),
+            package.bold()
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) =
+        Name::new(&Package::rade_home().join("log/install/")).set_reason(package, reason)
+    {
+        eprintln!("{} Failed to update the install log", ">>>".red().bold());
+        eprintln!("Error code: {}", e);
+        std::process::exit(1);
+    }
+    let reason = match reason {
+        Reason::Explicit => "explicitly installed",
+        Reason::Dependency => "installed as a dependency",
+    };
+    println!(
+        "{} {} marked as {}",
+        ">>>".green().bold(),
+        package.bold(),
+        reason
+    );
+}
+
 pub fn new() {
-    let ps = home_dir()
-        .expect("Failed to get home")
-        .join(".comrade/log/status");
+    let ps = Package::rade_home().join("log/status");
     if ps.exists() {
-        println!("Would you like to refresh the log?");
-        println!("This action will erase all previous logs");
-        print!("[y/n] ");
-        io::stdout().flush().unwrap();
-        let mut _str = String::new();
-        io::stdin().read_line(&mut _str).unwrap();
-        let _str = _str.trim();
-        if ["y", "yes", ""].contains(&_str) {
+        if prompt::confirm(
+            "Would you like to refresh the log?\nThis action will erase all previous logs",
+        ) {
             println!("{} Start creating log...", ">>>".blue().bold());
             println!("{} Deleting status file...", ">>>".green().bold());
-            fs::remove_file(
-                home_dir()
-                    .expect("Failed to get home dir.")
-                    .join(".comrade/log/status"),
-            )
-            .expect("Failed to remove file");
-            let utc = chrono::Utc::now();
-            let data = format!("{}-{}-{}", utc.year(), utc.month(), utc.day());
-            let time_ = utc.time().format("%H:%M:%S").to_string();
-            println!("{} Create log status...", ">>>".green().bold());
-            let config: ConfG = ConfG {
-                time: Time {
-                    utc_day: data,
-                    utc_time: time_,
-                },
-                install: 0,
-            };
-            let toml = toml::to_string(&config).unwrap();
-            fs::write(&ps, toml).unwrap();
-            println!("{}", "Log creation completed successfully".bold().cyan());
+            fs::remove_file(&ps).expect("Failed to remove file");
         } else {
             println!("Clearing the log has been canceled");
             std::process::exit(1);
         }
     } else {
         println!("{} Start creating log...", ">>>".blue().bold());
-        let utc = chrono::Utc::now();
-        let data = format!("{}-{}-{}", utc.year(), utc.month(), utc.day());
-        let time_ = utc.time().format("%H:%M:%S").to_string();
-        println!("{} Create log status...", ">>>".green().bold());
-        let config: ConfG = ConfG {
-            time: Time {
-                utc_day: data,
-                utc_time: time_,
-            },
-            install: 0,
-        };
-        let toml = toml::to_string(&config).unwrap();
-        fs::write(&ps, toml).unwrap();
-        println!("{}", "Log creation completed successfully".bold().cyan());
     }
+    println!("{} Create log status...", ">>>".green().bold());
+    write_status(0).unwrap();
+    println!("{}", "Log creation completed successfully".bold().cyan());
+}
+
+/// write a new `log/status` created now, counting `install` installations.
+pub fn write_status(install: i64) -> Result<(), Box<dyn Error>> {
+    let utc = Utc::now();
+    let config = ConfG {
+        schema: SCHEMA,
+        time: Time {
+            utc_day: format!("{}-{}-{}", utc.year(), utc.month(), utc.day()),
+            utc_time: utc.time().format("%H:%M:%S").to_string(),
+        },
+        install,
+    };
+    fs::write(
+        Package::rade_home().join("log/status"),
+        toml::to_string(&config)?,
+    )?;
+    Ok(())
+}
+
+/// check that `s` is a valid `log/status` and return its install counter.
+pub fn parse_status_str(s: &str) -> Result<i64, toml::de::Error> {
+    toml::from_str::<ConfG>(s).map(|c| c.install)
+}
+
+#[derive(Serialize)]
+struct Status {
+    created: Time,
+    installs: i64,
 }
 
 pub fn status() {
+    if output::is_json() {
+        let (install, utc_time, utc_day) = parse_status();
+        output::print_json(&Status {
+            created: Time { utc_day, utc_time },
+            installs: install.parse().unwrap_or_default(),
+        });
+        return;
+    }
     println!(
         "{} {}",
         ">>>".green().bold(),
@@ -183,6 +323,13 @@ pub fn status() {
 pub fn parse_status() -> (String, String, String) {
     let status_file = match fs::read_to_string(Package::rade_home().join("log/status")) {
         Ok(o) => o,
+        Err(e) if output::is_json() => output::fail(
+            "status_missing",
+            &format!(
+                "Failed to read status file: {}. Run `rade log new` to create it",
+                e
+            ),
+        ),
         Err(e) => {
             eprintln!(
                 "{} {}",
@@ -195,7 +342,13 @@ pub fn parse_status() -> (String, String, String) {
         }
     };
 
-    let toml: ConfG = toml::from_str(&status_file).unwrap();
+    let toml: ConfG = match toml::from_str(&status_file) {
+        Ok(toml) => toml,
+        Err(e) => output::fail(
+            "status_invalid",
+            &format!("Failed to parse status file: {}", e),
+        ),
+    };
 
     (
         toml.install.to_string(),
@@ -203,3 +356,197 @@ pub fn parse_status() -> (String, String, String) {
         toml.time.utc_day,
     )
 }
+
+/// where an executable in `bin/` came from, as far as `rade log rebuild` can tell.
+enum Found {
+    /// a copy of the install log kept under `versions/`
+    Kept(String, PathBuf),
+    /// a package list entry whose executable has the same name
+    Listed(String, Box<index::Entry>, Option<String>),
+}
+
+/// `versions/<package>/<version>` the link `bin` points into, if any.
+fn linked_version(home: &Path, bin: &Path) -> Option<(String, String)> {
+    let target = fs::read_link(bin).ok()?;
+    let mut parts = target.strip_prefix(home.join("versions")).ok()?.iter();
+    let package = parts.next()?.to_str()?.to_string();
+    let version = parts.next()?.to_str()?.to_string();
+    Some((package, version))
+}
+
+fn find(home: &Path, bin: &Path, exe: &str) -> Option<Found> {
+    let linked = linked_version(home, bin);
+    if let Some((package, version)) = &linked {
+        let log = home
+            .join("versions")
+            .join(package)
+            .join(version)
+            .join("log");
+        let kept: Option<Config> = fs::read_to_string(&log)
+            .ok()
+            .and_then(|s| toml::from_str(&s).ok());
+        if kept.is_some_and(|c| c.install.install_name == exe) {
+            return Some(Found::Kept(package.clone(), log));
+        }
+    }
+    let entries = index::entries();
+    let (package, entry) = match &linked {
+        Some((package, _)) => entries.into_iter().find(|(name, _)| name == package)?,
+        None => {
+            // packages built from source declare their executable in their own repository,
+            // so the package name is the best guess for them
+            let by_exec_name = entries
+                .iter()
+                .find(|(_, e)| e.exec_name.as_deref() == Some(exe))
+                .cloned();
+            by_exec_name.or_else(|| entries.into_iter().find(|(name, _)| name == exe))?
+        }
+    };
+    Some(Found::Listed(
+        package,
+        Box::new(entry),
+        linked.map(|(_, v)| v),
+    ))
+}
+
+/// `rade log rebuild`: recreate missing or broken install logs from the executables in `bin/`
+/// and recount `log/status`. Replaced files are kept under `log/backup/`.
+pub fn rebuild() {
+    let home = Package::rade_home();
+    let install_dir = home.join("log/install");
+    if let Err(e) = fs::create_dir_all(&install_dir) {
+        eprintln!(
+            "{} Failed to create {}",
+            ">>>".red().bold(),
+            install_dir.display()
+        );
+        eprintln!("Error code: {}", e);
+        std::process::exit(1);
+    }
+    println!(
+        "{} {}",
+        ">>>".green().bold(),
+        "Rebuilding the install logs...".bold()
+    );
+    let known: Vec<String> = logparser::install_records()
+        .into_iter()
+        .map(|r| r.executable)
+        .collect();
+    let backup = migrate::backup_dir();
+    let mut bins: Vec<PathBuf> = fs::read_dir(home.join("bin"))
+        .map(|dir| dir.flatten().map(|e| e.path()).collect())
+        .unwrap_or_default();
+    bins.sort();
+    let mut recovered = 0;
+    let mut unknown = Vec::new();
+    for bin in bins {
+        let exe = bin
+            .file_name()
+            .unwrap_or_default()
+            .to_string_lossy()
+            .to_string();
+        // rade itself has no install log
+        if exe == "rade" || exe.starts_with("rade.") || known.contains(&exe) {
+            continue;
+        }
+        let Some(found) = find(&home, &bin, &exe) else {
+            unknown.push(exe);
+            continue;
+        };
+        let package = match &found {
+            Found::Kept(package, _) | Found::Listed(package, _, _) => package.clone(),
+        };
+        let log = install_dir.join(&package);
+        let result = if log.exists() {
+            migrate::back_up(&log, &backup)
+        } else {
+            Ok(())
+        }
+        .and_then(|()| match found {
+            Found::Kept(_, kept) => {
+                fs::copy(&kept, &log)?;
+                println!(
+                    "{} {} restored from {}",
+                    ">>>".green().bold(),
+                    package.bold(),
+                    kept.display()
+                );
+                Ok(())
+            }
+            Found::Listed(_, entry, version) => {
+                let version = version.unwrap_or(entry.version);
+                Name::new(&install_dir)
+                    .dependencies(&entry.dependencies)
+                    .inferred(true)
+                    .write(&package, &exe, entry.repository, version.clone())?;
+                println!(
+                    "{} {} {} (inferred from the package list)",
+                    ">>>".yellow().bold(),
+                    package.bold(),
+                    version
+                );
+                Ok(())
+            }
+        });
+        match result {
+            Ok(()) => recovered += 1,
+            Err(e) => eprintln!(
+                "{} Failed to write the install log of {}: {}",
+                ">>>".red().bold(),
+                package,
+                e
+            ),
+        }
+    }
+    for exe in &unknown {
+        println!(
+            "{} {} does not match any package in the package list",
+            ">>>".yellow().bold(),
+            home.join("bin").join(exe).display()
+        );
+    }
+
+    // installations are counted from the journal when there is one
+    let installs = journal::entries()
+        .map(|entries| {
+            entries
+                .iter()
+                .filter(|e| e.action == Action::Install && e.outcome == Outcome::Success)
+                .count()
+        })
+        .unwrap_or_default()
+        .max(logparser::install_records().len()) as i64;
+    let status = home.join("log/status");
+    let current: Option<ConfG> = fs::read_to_string(&status)
+        .ok()
+        .and_then(|s| toml::from_str(&s).ok());
+    let written = match current {
+        Some(mut current) => {
+            current.install = installs;
+            current.schema = SCHEMA;
+            toml::to_string(&current)
+                .map_err(|e| e.into())
+                .and_then(|s| fs::write(&status, s).map_err(|e| e.into()))
+        }
+        None if status.exists() => {
+            migrate::back_up(&status, &backup).and_then(|()| write_status(installs))
+        }
+        None => write_status(installs),
+    };
+    if let Err(e) = written {
+        eprintln!(
+            "{} Failed to write {}",
+            ">>>".red().bold(),
+            status.display()
+        );
+        eprintln!("Error code: {}", e);
+        std::process::exit(1);
+    }
+    println!(
+        "{} Recovered {} install log(s); {} installation(s) counted",
+        ">>>".green().bold(),
+        recovered,
+        installs
+    );
+    println!("{}", "Log rebuild completed".bold().cyan());
+}
@@ -2,37 +2,89 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
-use crate::Package;
+use crate::{log::Reason, output, Package};
 use colored::*;
 use dirs::home_dir;
 use serde::Deserialize;
+use std::error::Error;
 use std::ffi::OsStr;
 use std::fs;
 
+/// an install record, read from `log/install/<package>`.
+#[derive(Debug)]
+pub struct InstallRecord {
+    pub package: String,
+    pub executable: String,
+    pub version: String,
+    pub repository: String,
+    pub utc_day: String,
+    pub utc_time: String,
+    pub dependencies: Vec<String>,
+    pub reason: Reason,
+    /// commit or archive hash, missing in logs written before it was recorded
+    pub source: Option<String>,
+    /// written by `rade log rebuild`, which had to guess the version
+    pub inferred: bool,
+    /// the `Radefile.toml` registry the package came from, `None` for the package list
+    pub registry: Option<String>,
+}
+
 #[derive(Debug, Deserialize)]
 struct Time {
-    #[allow(unused)]
     utc_day: String,
-    #[allow(unused)]
     utc_time: String,
 }
 #[derive(Deserialize, Debug)]
 struct Install {
     install_name: String,
+    /// missing in logs written before install reasons were recorded
+    #[serde(default)]
+    reason: Reason,
 }
 #[derive(Deserialize, Debug)]
 struct Info {
     version: String,
     repositry: String,
+    /// missing in logs written before dependencies were recorded
+    dependencies: Option<Vec<String>>,
+    #[serde(default)]
+    source: Option<String>,
+    #[serde(default)]
+    inferred: bool,
+    #[serde(default)]
+    registry: Option<String>,
 }
 #[derive(Deserialize, Debug)]
 struct Config {
-    #[allow(unused)]
     time: Time,
     install: Install,
     info: Info,
 }
 
+/// installed packages that depend on `package`, sorted by name.
+pub fn dependents(package: &str) -> Vec<String> {
+    install_records()
+        .into_iter()
+        .filter(|r| r.package != package && r.dependencies.iter().any(|d| d == package))
+        .map(|r| r.package)
+        .collect()
+}
+
+/// every readable install record, sorted by package name.
+pub fn install_records() -> Vec<InstallRecord> {
+    let dir = match fs::read_dir(Package::rade_home().join("log/install")) {
+        Ok(dir) => dir,
+        Err(_) => return Vec::new(),
+    };
+    let mut records: Vec<InstallRecord> = dir
+        .flatten()
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .filter_map(|name| Package::read_install_record(&name).ok())
+        .collect();
+    records.sort_by(|a, b| a.package.cmp(&b.package));
+    records
+}
+
 pub fn program_exists(packagename: &str) -> bool {
     let dir_path = dirs::home_dir()
         .expect("Failed to get home directory")
@@ -41,7 +93,7 @@ pub fn program_exists(packagename: &str) -> bool {
         Ok(dir) => dir,
         Err(e) => {
             eprintln!(
-                "{}{}Please report this issue to the comrade package manager repositoryError code: {}",
+                "{}{}Run `rade log rebuild` to recreate them from the installed executables.\nError code: {}",
                 ">>> ".red().bold(),
                 "Failed to read log files.\n".bold(),
                 e
@@ -74,42 +126,73 @@ impl Package {
     /// # log_parse
     /// log paeser.
     /// ## usage
-    /// `Package::log_parse(_package_name_)`
+    /// `Package::log_parse(_package_name_)?`
     ///
     /// ## return
     /// this function is return the taple
     /// `(String, String, String)`
     /// return list is
-    /// `(executable_name, repositry_url, package_version)`
-    pub fn log_parse(packagename: &str) -> (String, String, String) {
+    /// `(executable_name, repositry_url, package_version)`.
+    /// A missing or broken log is returned as an error.
+    pub fn log_parse(packagename: &str) -> Result<(String, String, String), Box<dyn Error>> {
         let installdir = home_dir()
             .expect("Failed to get home dir")
             .join(".comrade/log/install/");
-        let base = format!("{}{}", installdir.display(), &packagename);
-        let toml_file = match fs::read_to_string(base) {
-            Ok(o) => o,
-            Err(e) => {
-                eprintln!("Failed to read package log.");
-                eprintln!("Error code: {}", e);
-                e.to_string()
-            }
-        };
-        let cofg: Config = match toml::from_str(&toml_file) {
-            Ok(cofg) => cofg,
-            Err(_) => {
-                eprintln!("{} {}", ">>>".red().bold(), "The log format is old.".bold());
-                eprintln!("The current rade log format is toml, but the log format on this computer is the past rade log format");
-                eprintln!("Run rm -rf ~/.comrade and reinstall rade");
-                eprintln!("{} {}","Note:".bold(),"This action will delete not only rade but all programs previously installed with rade.".red());
-                eprintln!("After accepting this, execute the previous command.");
-                std::process::exit(1);
-            }
-        };
+        let cofg: Config = fs::read_to_string(installdir.join(packagename))
+            .map_err(|e| e.to_string())
+            .and_then(|s| toml::from_str(&s).map_err(|e| e.to_string()))
+            .map_err(|e| {
+                format!(
+                    "Failed to read the install log of {} (run `rade doctor` to see how to repair it): {}",
+                    packagename, e
+                )
+            })?;
 
-        (
+        Ok((
             cofg.install.install_name,
             cofg.info.version,
             cofg.info.repositry,
-        )
+        ))
+    }
+    /// # installed_version
+    /// version recorded in the install log, or `None` if the package is not installed.
+    /// A log that cannot be read is reported (as JSON with `--format json`) and ends the process.
+    pub fn installed_version(packagename: &str) -> Option<String> {
+        let log = Package::rade_home().join("log/install/").join(packagename);
+        if !log.exists() {
+            return None;
+        }
+        match Package::log_parse(packagename) {
+            Ok((_, version, _)) => Some(version),
+            Err(e) => output::fail("log_invalid", &e.to_string()),
+        }
+    }
+    /// # read_install_record
+    /// read the install log of a package. Unlike `log_parse`, failures are returned.
+    pub fn read_install_record(packagename: &str) -> Result<InstallRecord, Box<dyn Error>> {
+        let path = Package::rade_home().join("log/install/").join(packagename);
+        let cofg: Config = toml::from_str(&fs::read_to_string(path)?)?;
+        Ok(InstallRecord {
+            package: packagename.to_string(),
+            executable: cofg.install.install_name,
+            reason: cofg.install.reason,
+            version: cofg.info.version,
+            repository: cofg.info.repositry,
+            source: cofg.info.source,
+            inferred: cofg.info.inferred,
+            registry: cofg.info.registry,
+            utc_day: cofg.time.utc_day,
+            utc_time: cofg.time.utc_time,
+            dependencies: match cofg.info.dependencies {
+                Some(dependencies) => dependencies,
+                // best effort for old logs: what the package list says today
+                None => Package::read_package_info(packagename)
+                    .map(|i| i.dependencies)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|d| !d.is_empty())
+                    .collect(),
+            },
+        })
     }
 }
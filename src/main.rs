@@ -2,79 +2,305 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
+mod buildlog;
+mod config;
+mod doctor;
 mod download_install;
+mod env;
+mod export;
 mod gitl;
+mod index;
 mod info;
 mod install;
+mod journal;
 mod list;
 mod log;
 mod logparser;
+mod migrate;
+mod output;
+mod pkg;
+mod prompt;
+mod release;
 mod remove;
 mod search;
-use clap::{Parser, Subcommand, ValueEnum};
+mod source_install;
+mod sync;
+mod toolchain;
+mod upgrade;
+mod version;
+mod versions;
+mod which;
+use clap::{Parser, Subcommand};
 use colored::Colorize;
+use env::Shell;
+use list::SortKey;
+use log::Reason;
+use output::Format;
+use prompt::Assume;
+use std::path::PathBuf;
 
-#[derive(Subcommand, ValueEnum, Clone)]
+#[derive(Subcommand)]
+enum Pkg {
+    /// Create a package skeleton in ./<name>
+    New {
+        name: String,
+        /// Repository the package is built from
+        #[arg(long)]
+        repository: Option<String>,
+    },
+    /// Check a package for mistakes before publishing it
+    Lint {
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Build the package and pack it as <name>-<target>.radepkg
+    Build {
+        #[arg(default_value = ".")]
+        dir: PathBuf,
+        /// Directory the archive and its .sha256 are written to
+        #[arg(short, long, default_value = ".")]
+        output: PathBuf,
+    },
+}
+
+#[derive(Subcommand, Clone)]
 enum Logs {
+    /// Show the install counter of log/status
     Status,
+    /// Write a new log/status
     New,
     Search,
+    /// Recreate missing install logs and log/status from the installed executables
+    Rebuild,
+    /// Show the newest build log of a package, or list the build logs
+    Build {
+        package: Option<String>,
+    },
 }
 pub struct Package;
 
 #[derive(Parser)]
 #[command(version = "0.8.4")]
 /// A simple, fast, and safe package manager
-enum Cli {
+struct Cli {
+    /// Answer yes to every prompt
+    #[arg(short, long, global = true, conflicts_with = "no")]
+    yes: bool,
+    /// Answer no to every prompt
+    #[arg(long, global = true)]
+    no: bool,
+    /// Keep the build directory in ~/.comrade/kept-builds when install.sh fails
+    #[arg(long, global = true)]
+    keep_build: bool,
+    /// Output format (json is supported by list, info, search, outdated and log status)
+    #[arg(long, global = true, value_enum, default_value_t = Format::Text)]
+    format: Format,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Subcommand)]
+enum Command {
     /// Update the package list
     Update,
-    /// Upgrade the knife tool
-    Upgrade,
+    /// Show installed packages that have a newer version in the package list
+    Outdated,
+    /// Upgrade installed packages
+    Upgrade {
+        /// The packages to upgrade
+        packages: Vec<String>,
+        /// Upgrade every outdated package
+        #[arg(short, long)]
+        all: bool,
+        /// Also go back to an older version when the package list has one
+        #[arg(long)]
+        allow_downgrade: bool,
+    },
+    /// Upgrade rade itself
+    SelfUpdate {
+        /// Only show whether a newer version is published
+        #[arg(long)]
+        check: bool,
+        /// Install this published version instead of the newest one
+        #[arg(long, value_name = "VERSION")]
+        to: Option<String>,
+        /// Also go back to an older version
+        #[arg(long)]
+        allow_downgrade: bool,
+    },
     /// Lists the packages
     List {
+        /// List the installed packages instead
         #[arg(short, long)]
         installed: bool,
+        /// Sort installed packages by this key
+        #[arg(long, value_enum, default_value_t = SortKey::Name, requires = "installed")]
+        sort: SortKey,
+        /// Only show installed packages whose name matches (`*` and `?` are wildcards)
+        #[arg(long, requires = "installed")]
+        filter: Option<String>,
+        /// Highlight installed packages with a newer version in the package list
+        #[arg(long, requires = "installed")]
+        outdated: bool,
     },
+    /// Show a package from the package list
+    Info { package: String },
+    /// Search the package list by name
+    Search { query: String },
     /// Install a package
     Install {
-        /// The package name (for install command)
+        /// The package name, a local path, file:///path or git+<url>[#rev]
         package: String,
         #[arg(short, long, action = clap::ArgAction::SetTrue)]
         build: bool,
     },
     /// Rade log managements
-    Log { logs: Logs },
+    Log {
+        #[command(subcommand)]
+        logs: Logs,
+    },
+    /// Package authoring tools
+    Pkg {
+        #[command(subcommand)]
+        command: Pkg,
+    },
     /// Remove the package
-    Remove { package: String },
+    Remove {
+        package: String,
+        /// Also remove the installed packages that depend on it
+        #[arg(long, conflicts_with = "force")]
+        cascade: bool,
+        /// Remove it even if installed packages depend on it
+        #[arg(long)]
+        force: bool,
+    },
+    /// Remove packages installed as dependencies that are no longer required
+    Autoremove,
+    /// Change why a package is considered installed
+    #[command(group = clap::ArgGroup::new("reason").required(true))]
+    Mark {
+        package: String,
+        /// The package was asked for, so autoremove keeps it
+        #[arg(long, group = "reason")]
+        explicit: bool,
+        /// The package is only needed by other packages
+        #[arg(long, group = "reason")]
+        dependency: bool,
+    },
+    /// Install, change or remove packages to match a Radefile.toml
+    Sync {
+        /// The manifest to read
+        #[arg(short, long, default_value = "Radefile.toml")]
+        file: PathBuf,
+        /// Also remove explicitly installed packages the manifest does not list
+        #[arg(long)]
+        prune: bool,
+        /// Only show what would change
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Write the installed packages, their versions and sources to a TOML file
+    Export {
+        /// Where to write it; stdout when omitted
+        file: Option<PathBuf>,
+    },
+    /// Install the package set written by `rade export`
+    Import { file: PathBuf },
+    /// Print the shell setup for rade's directories and the installed packages
+    Env {
+        /// Shell to write for (defaults to the one in $SHELL)
+        #[arg(long, value_enum)]
+        shell: Option<Shell>,
+        /// Add the setup to the shell's rc file
+        #[arg(long)]
+        install: bool,
+    },
+    /// Check the rade installation for problems
+    Doctor {
+        /// Apply the fixes that cannot lose anything
+        #[arg(long)]
+        fix: bool,
+    },
+    /// Switch the active version of an installed package
+    Use {
+        /// <package>@<version>
+        spec: String,
+    },
+    /// Restore a previously installed version of the package
+    Rollback {
+        package: String,
+        /// The version to restore (defaults to the one installed before the current one)
+        version: Option<String>,
+    },
 }
 
 fn main() {
     let version = info::VERSION;
     let args = Cli::parse();
-    match args {
-        Cli::Update => {
+    prompt::init(if args.yes {
+        Some(Assume::Yes)
+    } else if args.no {
+        Some(Assume::No)
+    } else {
+        None
+    });
+    output::init(args.format);
+    buildlog::init(args.keep_build);
+    migrate::run();
+    match args.command {
+        Command::Update => {
             Package::update_package_list();
             std::process::exit(0);
         }
-        Cli::Upgrade => {
-            gitl::upgrade_rade(version.to_string());
+        Command::Outdated => {
+            upgrade::outdated();
+        }
+        Command::Upgrade {
+            packages,
+            all,
+            allow_downgrade,
+        } => {
+            upgrade::upgrade(packages, all, allow_downgrade);
+        }
+        Command::SelfUpdate {
+            check,
+            to,
+            allow_downgrade,
+        } => {
+            release::self_update(version, check, to, allow_downgrade);
+        }
+        Command::Info { package } => {
+            info::show(&package);
+        }
+        Command::Search { query } => {
+            search::search(&query);
         }
-        Cli::Install { package, build } => {
+        Command::Install { package, build } => {
             if build {
                 println!("{} {}", ">>>".yellow().bold(), "Selected build".bold());
-                Package::install(&package, false, true);
+            }
+            let installed = if source_install::Source::parse(&package).is_some() {
+                Package::install_from_source(&package)
             } else {
-                Package::install(&package, false, false);
+                Package::install(&package, false, build)
+            };
+            if installed.is_err() {
+                std::process::exit(1);
             }
         }
-        Cli::List { installed } => {
+        Command::List {
+            installed,
+            sort,
+            filter,
+            outdated,
+        } => {
             if installed {
-                list::list("bin", true);
+                list::installed(sort, filter, outdated);
             } else {
-                list::list("packagelist", false);
+                list::list();
             }
         }
-        Cli::Log { logs } => match logs {
+        Command::Log { logs } => match logs {
             Logs::New => {
                 log::new();
             }
@@ -84,9 +310,72 @@ fn main() {
             Logs::Search => {
                 todo!();
             }
+            Logs::Rebuild => {
+                log::rebuild();
+            }
+            Logs::Build { package } => {
+                buildlog::show(package.as_deref());
+            }
+        },
+        Command::Pkg { command } => match command {
+            Pkg::New { name, repository } => {
+                pkg::new(&name, repository.as_deref());
+            }
+            Pkg::Lint { dir } => {
+                pkg::lint(&dir);
+            }
+            Pkg::Build { dir, output } => {
+                pkg::build(&dir, &output);
+            }
         },
-        Cli::Remove { package } => {
-            Package::remove(&package, false);
+        Command::Remove {
+            package,
+            cascade,
+            force,
+        } => {
+            Package::remove_checked(&package, cascade, force);
+        }
+        Command::Autoremove => {
+            Package::autoremove();
+        }
+        Command::Mark {
+            package,
+            explicit,
+            dependency: _,
+        } => {
+            log::mark(
+                &package,
+                if explicit {
+                    Reason::Explicit
+                } else {
+                    Reason::Dependency
+                },
+            );
+        }
+        Command::Sync {
+            file,
+            prune,
+            dry_run,
+        } => {
+            sync::sync(&file, prune, dry_run);
+        }
+        Command::Export { file } => {
+            export::export(file.as_deref());
+        }
+        Command::Import { file } => {
+            export::import(&file);
+        }
+        Command::Env { shell, install } => {
+            env::env(shell, install);
+        }
+        Command::Doctor { fix } => {
+            doctor::doctor(fix);
+        }
+        Command::Use { spec } => {
+            Package::switch(&spec);
+        }
+        Command::Rollback { package, version } => {
+            Package::rollback(&package, version);
         }
     }
 }
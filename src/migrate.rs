@@ -0,0 +1,413 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{index, log, log::Reason, logparser, Package};
+use chrono::{DateTime, Datelike, Utc};
+use colored::*;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// schema of the install logs and `log/status` this version writes.
+/// 0: plain text from before the logs were TOML, see `parse_legacy`.
+/// 1: no `schema` field; logs may lack `info.dependencies` and `install.reason`.
+/// 2: `schema` is recorded and every field is present.
+pub const SCHEMA: i64 = 2;
+
+/// schema of a parsed log; files from before schemas were recorded count as 1.
+fn schema(table: &toml::Table) -> i64 {
+    table
+        .get("schema")
+        .and_then(|s| s.as_integer())
+        .unwrap_or(1)
+}
+
+/// fields of an install log written before rade used TOML.
+#[derive(Default, Debug, PartialEq)]
+struct Legacy {
+    day: Option<String>,
+    time: Option<String>,
+    executable: Option<String>,
+    version: Option<String>,
+    repository: Option<String>,
+}
+
+/// read a plain text install log: one field per line, either labelled
+/// (`version: 1.0.0`, `version = "1.0.0"`) or bare. Bare lines are told apart by their
+/// shape: a `2024-5-1` date, a `12:00:00` time, a URL or path, a version and the executable.
+fn parse_legacy(text: &str) -> Legacy {
+    let mut legacy = Legacy::default();
+    for line in text.lines().map(str::trim) {
+        if line.is_empty() || line.starts_with(['#', '[']) {
+            continue;
+        }
+        let labelled = line.split_once([':', '=']).and_then(|(label, value)| {
+            let field = match label.trim().to_lowercase().as_str() {
+                "utc_day" | "day" | "date" => &mut legacy.day,
+                "utc_time" | "time" => &mut legacy.time,
+                "install_name" | "executable" | "exe" | "exec_name" | "name" => {
+                    &mut legacy.executable
+                }
+                "version" | "ver" => &mut legacy.version,
+                "repositry" | "repository" | "repo" | "url" => &mut legacy.repository,
+                _ => return None,
+            };
+            Some((field, value))
+        });
+        let (field, value) = match labelled {
+            Some(labelled) => labelled,
+            None => {
+                let field = if is_date(line) {
+                    &mut legacy.day
+                } else if is_time(line) {
+                    &mut legacy.time
+                } else if line.contains("://")
+                    || line.starts_with(['/', '~'])
+                    || line.starts_with("git@")
+                {
+                    &mut legacy.repository
+                } else if line
+                    .strip_prefix(['v', 'V'])
+                    .unwrap_or(line)
+                    .starts_with(|c: char| c.is_ascii_digit())
+                {
+                    &mut legacy.version
+                } else {
+                    &mut legacy.executable
+                };
+                (field, line)
+            }
+        };
+        let value = value.trim().trim_matches(['"', '\'']).trim();
+        if field.is_none() && !value.is_empty() {
+            *field = Some(value.to_string());
+        }
+    }
+    legacy
+}
+
+/// `true` for `<number>-<number>-<number>`.
+fn is_date(s: &str) -> bool {
+    let parts: Vec<&str> = s.split('-').collect();
+    parts.len() == 3
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// `true` for `<number>:<number>` or `<number>:<number>:<number>`.
+fn is_time(s: &str) -> bool {
+    let parts: Vec<&str> = s.split(':').collect();
+    (2..=3).contains(&parts.len())
+        && parts
+            .iter()
+            .all(|p| !p.is_empty() && p.chars().all(|c| c.is_ascii_digit()))
+}
+
+/// turn a plain text install log of `package` into a schema 1 table.
+/// What the log does not say is taken from the package list, and a version found
+/// there is marked as inferred. `modified` stands in for a missing install time.
+fn legacy_table(
+    package: &str,
+    legacy: Legacy,
+    modified: DateTime<Utc>,
+) -> Result<toml::Table, Box<dyn Error>> {
+    if legacy.version.is_none() && legacy.executable.is_none() && legacy.repository.is_none() {
+        return Err("not an install log".into());
+    }
+    let listed = index::get(package);
+    let inferred = legacy.version.is_none();
+    let version = legacy
+        .version
+        .or_else(|| listed.as_ref().map(|e| e.version.clone()))
+        .ok_or("the log has no version and the package list does not know the package")?;
+    let repository = legacy
+        .repository
+        .or_else(|| listed.as_ref().map(|e| e.repository.clone()))
+        .unwrap_or_default();
+
+    let mut time = toml::Table::new();
+    time.insert(
+        "utc_day".to_string(),
+        toml::Value::String(legacy.day.unwrap_or_else(|| {
+            format!(
+                "{}-{}-{}",
+                modified.year(),
+                modified.month(),
+                modified.day()
+            )
+        })),
+    );
+    time.insert(
+        "utc_time".to_string(),
+        toml::Value::String(
+            legacy
+                .time
+                .unwrap_or_else(|| modified.time().format("%H:%M:%S").to_string()),
+        ),
+    );
+    let mut install = toml::Table::new();
+    install.insert(
+        "install_name".to_string(),
+        toml::Value::String(legacy.executable.unwrap_or_else(|| package.to_string())),
+    );
+    let mut info = toml::Table::new();
+    info.insert("version".to_string(), toml::Value::String(version));
+    info.insert("repositry".to_string(), toml::Value::String(repository));
+    if inferred {
+        info.insert("inferred".to_string(), toml::Value::Boolean(true));
+    }
+
+    let mut table = toml::Table::new();
+    table.insert("time".to_string(), toml::Value::Table(time));
+    table.insert("install".to_string(), toml::Value::Table(install));
+    table.insert("info".to_string(), toml::Value::Table(info));
+    Ok(table)
+}
+
+/// read the install log of `package` at `file` as a table, converting the plain text format.
+fn read_log(package: &str, file: &Path) -> Result<toml::Table, Box<dyn Error>> {
+    let text = fs::read_to_string(file)?;
+    match toml::from_str::<toml::Table>(&text) {
+        Ok(table) => Ok(table),
+        Err(e) => {
+            let modified = file
+                .metadata()
+                .and_then(|m| m.modified())
+                .map(DateTime::<Utc>::from)
+                .unwrap_or_else(|_| Utc::now());
+            legacy_table(package, parse_legacy(&text), modified)
+                .map_err(|legacy| format!("{}; nor is it a plain text log: {}", e, legacy).into())
+        }
+    }
+}
+
+/// `true` when `file` is an install log of an older schema that can be migrated.
+pub fn is_old(package: &str, file: &Path) -> bool {
+    read_log(package, file).is_ok_and(|t| schema(&t) < SCHEMA)
+}
+
+/// bring one install log up to `SCHEMA`, filling in what older versions did not record.
+/// Returns `false` if it was already current.
+fn upgrade_log(package: &str, table: &mut toml::Table) -> Result<bool, Box<dyn Error>> {
+    if schema(table) >= SCHEMA {
+        return Ok(false);
+    }
+    let install = table
+        .get_mut("install")
+        .and_then(|i| i.as_table_mut())
+        .ok_or("no [install] table")?;
+    install
+        .entry("reason")
+        .or_insert(toml::Value::try_from(Reason::Explicit)?);
+    let info = table
+        .get_mut("info")
+        .and_then(|i| i.as_table_mut())
+        .ok_or("no [info] table")?;
+    if !info.contains_key("dependencies") {
+        // the best guess is what the package list says today
+        let dependencies: Vec<String> = Package::read_package_info(package)
+            .map(|i| i.dependencies)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|d| !d.is_empty())
+            .collect();
+        info.insert(
+            "dependencies".to_string(),
+            toml::Value::try_from(dependencies)?,
+        );
+    }
+    table.insert("schema".to_string(), toml::Value::Integer(SCHEMA));
+    Ok(true)
+}
+
+/// a directory under `log/backup/` for the files changed by this run.
+pub fn backup_dir() -> PathBuf {
+    Package::rade_home()
+        .join("log/backup")
+        .join(Utc::now().format("%Y%m%dT%H%M%S").to_string())
+}
+
+/// copy `file` into `backup` under the same path relative to the rade home.
+pub fn back_up(file: &Path, backup: &Path) -> Result<(), Box<dyn Error>> {
+    let relative = file.strip_prefix(Package::rade_home()).unwrap_or(file);
+    let copy = backup.join(relative);
+    if let Some(dir) = copy.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::copy(file, copy)?;
+    Ok(())
+}
+
+/// migrate the install log at `file` of `package` in place, keeping a copy in `backup`.
+fn migrate_log(package: &str, file: &Path, backup: &Path) -> Result<bool, Box<dyn Error>> {
+    let mut table = read_log(package, file)?;
+    if !upgrade_log(package, &mut table)? {
+        return Ok(false);
+    }
+    back_up(file, backup)?;
+    fs::write(file, toml::to_string(&table)?)?;
+    Ok(true)
+}
+
+/// every install log: `log/install/<package>` and the copies kept in `versions/<package>/<version>/log`.
+fn log_files() -> Vec<(String, PathBuf)> {
+    let home = Package::rade_home();
+    let mut files = Vec::new();
+    if let Ok(dir) = fs::read_dir(home.join("log/install")) {
+        for entry in dir.flatten() {
+            files.push((
+                entry.file_name().to_string_lossy().to_string(),
+                entry.path(),
+            ));
+        }
+    }
+    if let Ok(dir) = fs::read_dir(home.join("versions")) {
+        for package in dir.flatten() {
+            let name = package.file_name().to_string_lossy().to_string();
+            for version in fs::read_dir(package.path()).into_iter().flatten().flatten() {
+                let log = version.path().join("log");
+                if log.is_file() {
+                    files.push((name.clone(), log));
+                }
+            }
+        }
+    }
+    files
+}
+
+/// migrate one install log, for `rade doctor --fix`.
+pub fn migrate_file(package: &str, file: &Path) -> Result<(), Box<dyn Error>> {
+    migrate_log(package, file, &backup_dir())?;
+    Ok(())
+}
+
+/// run at startup: when `log/status` is older than `SCHEMA`, every install log is migrated,
+/// then the status file. A status file that is not TOML, or a missing one next to
+/// install logs, counts as old. The status is only marked current once every log was
+/// migrated, so logs that failed are tried again on the next start.
+/// Messages go to stderr, which keeps the output of commands like `rade env` usable.
+pub fn run() {
+    let home = Package::rade_home();
+    let status = home.join("log/status");
+    let table = match fs::read_to_string(&status) {
+        Ok(s) => toml::from_str::<toml::Table>(&s).ok(),
+        // a new rade home has no logs to migrate; `rade log new` writes the status
+        Err(_) if !has_logs(&home) => return,
+        Err(_) => None,
+    };
+    if table.as_ref().is_some_and(|t| schema(t) >= SCHEMA) {
+        return;
+    }
+
+    let backup = backup_dir();
+    let mut migrated = 0;
+    let mut failed = 0;
+    for (package, file) in log_files() {
+        match migrate_log(&package, &file, &backup) {
+            Ok(true) => migrated += 1,
+            Ok(false) => {}
+            Err(e) => {
+                failed += 1;
+                eprintln!(
+                    "{} Cannot migrate {}: {}",
+                    ">>>".yellow().bold(),
+                    file.display(),
+                    e
+                );
+            }
+        }
+    }
+    if migrated > 0 {
+        eprintln!(
+            "{} Migrated {} install log(s) to the current format; the old files are in {}",
+            ">>>".green().bold(),
+            migrated,
+            backup.display()
+        );
+    }
+    if failed > 0 {
+        eprintln!("Run `rade doctor` to see how to repair the others.");
+        return;
+    }
+
+    let result = (|| -> Result<(), Box<dyn Error>> {
+        if status.exists() {
+            back_up(&status, &backup)?;
+        }
+        match table {
+            Some(mut table) => {
+                table.insert("schema".to_string(), toml::Value::Integer(SCHEMA));
+                fs::write(&status, toml::to_string(&table)?)?;
+            }
+            // the old counter cannot be read, so it starts from what is installed
+            None => log::write_status(logparser::install_records().len() as i64)?,
+        }
+        Ok(())
+    })();
+    if let Err(e) = result {
+        eprintln!(
+            "{} Failed to migrate {}: {}",
+            ">>>".yellow().bold(),
+            status.display(),
+            e
+        );
+    }
+}
+
+/// `true` if `log/install` has any file in it.
+fn has_logs(home: &Path) -> bool {
+    fs::read_dir(home.join("log/install")).is_ok_and(|mut dir| dir.next().is_some())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn legacy(day: &str, time: &str, executable: &str, version: &str, repository: &str) -> Legacy {
+        let field = |s: &str| Some(s.to_string()).filter(|s| !s.is_empty());
+        Legacy {
+            day: field(day),
+            time: field(time),
+            executable: field(executable),
+            version: field(version),
+            repository: field(repository),
+        }
+    }
+
+    #[test]
+    fn parse_legacy_logs() {
+        let cases = [
+            // bare lines, told apart by their shape
+            (
+                "2024-5-1\n12:30:00\nhello\n1.0.0\nhttps://github.com/a/hello\n",
+                legacy("2024-5-1", "12:30:00", "hello", "1.0.0", "https://github.com/a/hello"),
+            ),
+            (
+                "https://github.com/a/hello\nv2.1\nhello-cli\n",
+                legacy("", "", "hello-cli", "v2.1", "https://github.com/a/hello"),
+            ),
+            // labelled lines, with `:` or `=` and optional quotes
+            (
+                "install_name: hello\nversion: 1.0.0\nrepositry: https://github.com/a/hello\nutc_time: 08:00:01\n",
+                legacy("", "08:00:01", "hello", "1.0.0", "https://github.com/a/hello"),
+            ),
+            (
+                "[info]\nversion = \"0.3\nrepository = '/srv/hello'\n",
+                legacy("", "", "", "0.3", "/srv/hello"),
+            ),
+            ("\n# nothing here\n", legacy("", "", "", "", "")),
+        ];
+        for (text, expected) in cases {
+            assert_eq!(parse_legacy(text), expected, "parse_legacy({:?})", text);
+        }
+    }
+
+    #[test]
+    fn legacy_table_needs_a_field() {
+        assert!(legacy_table("hello", Legacy::default(), Utc::now()).is_err());
+    }
+}
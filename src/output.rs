@@ -0,0 +1,62 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use clap::ValueEnum;
+use colored::*;
+use serde::Serialize;
+use std::sync::OnceLock;
+
+/// output format selected with `--format`.
+/// The JSON schemas are documented in README.md and must stay backwards compatible:
+/// fields may be added, never renamed or removed.
+#[derive(ValueEnum, Clone, Copy, PartialEq, Default, Debug)]
+pub enum Format {
+    #[default]
+    Text,
+    Json,
+}
+
+static FORMAT: OnceLock<Format> = OnceLock::new();
+
+/// set the output format for this run.
+pub fn init(format: Format) {
+    let _ = FORMAT.set(format);
+}
+
+/// `true` with `--format json`.
+pub fn is_json() -> bool {
+    FORMAT.get().copied().unwrap_or_default() == Format::Json
+}
+
+/// print `value` as JSON on stdout.
+pub fn print_json<T: Serialize>(value: &T) {
+    match serde_json::to_string_pretty(value) {
+        Ok(s) => println!("{}", s),
+        Err(e) => fail("internal", &format!("failed to serialize output: {}", e)),
+    }
+}
+
+#[derive(Serialize)]
+struct ErrorObject<'a> {
+    error: ErrorBody<'a>,
+}
+
+#[derive(Serialize)]
+struct ErrorBody<'a> {
+    code: &'a str,
+    message: &'a str,
+}
+
+/// report a failure and exit with status 1.
+/// With `--format json` this prints `{"error": {"code": ..., "message": ...}}` on stdout.
+pub fn fail(code: &str, message: &str) -> ! {
+    if is_json() {
+        print_json(&ErrorObject {
+            error: ErrorBody { code, message },
+        });
+    } else {
+        eprintln!("{} {}", ">>>".red().bold(), message.bold());
+    }
+    std::process::exit(1);
+}
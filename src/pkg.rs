@@ -0,0 +1,511 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    env, index,
+    install::{self, PackageInfo},
+    journal, release, sync, toolchain, Package,
+};
+use colored::*;
+use git2::{Direction, Remote, Repository};
+use std::{
+    error::Error,
+    fs,
+    io::Write,
+    path::{Path, PathBuf},
+    process,
+};
+use zip::{write::FileOptions, CompressionMethod, DateTime, ZipWriter};
+
+/// fields of `package.toml` and the TOML type each must have.
+const FIELDS: [(&str, &str); 6] = [
+    ("dependencies", "array"),
+    ("language", "string"),
+    ("repository", "string"),
+    ("capacity", "integer"),
+    ("version", "string"),
+    ("download", "boolean"),
+];
+
+/// fields `package.toml` may have.
+const OPTIONAL_FIELDS: [(&str, &str); 5] = [
+    ("description", "string"),
+    ("targets", "array"),
+    ("env", "table"),
+    ("build_requires", "array"),
+    ("requires_commands", "array"),
+];
+
+/// "a" or "an" before the TOML type `kind`.
+fn article(kind: &str) -> &'static str {
+    if kind == "array" || kind == "integer" {
+        "an"
+    } else {
+        "a"
+    }
+}
+
+/// how bad a lint finding is. Errors make `rade pkg lint` fail.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+#[derive(Debug)]
+pub struct Finding {
+    pub severity: Severity,
+    pub message: String,
+}
+
+fn error(message: String) -> Finding {
+    Finding {
+        severity: Severity::Error,
+        message,
+    }
+}
+
+fn warning(message: String) -> Finding {
+    Finding {
+        severity: Severity::Warning,
+        message,
+    }
+}
+
+/// `rade pkg new`: scaffold a package in `./<name>/`.
+///
+/// `package.toml` is the entry for the package list;
+/// `install.sh` and `.comrade/exe_name` belong in the package's repository.
+pub fn new(name: &str, repository: Option<&str>) {
+    let dir = PathBuf::from(name);
+    if dir.exists() {
+        eprintln!(
+            "{} {} already exists",
+            ">>>".red().bold(),
+            dir.display().to_string().bold()
+        );
+        std::process::exit(1);
+    }
+    if let Err(e) = scaffold(&dir, name, repository.unwrap_or("")) {
+        eprintln!("{} Failed to create {}", ">>>".red().bold(), dir.display());
+        eprintln!("Error code: {}", e);
+        std::process::exit(1);
+    }
+    println!(
+        "{} {} {}",
+        ">>>".green().bold(),
+        "Created package".bold(),
+        name.bold()
+    );
+    println!("  {}/package.toml       entry for the package list", name);
+    println!("  {}/install.sh         builds the executable", name);
+    println!("  {}/.comrade/exe_name  name of the executable", name);
+    println!("Check it with `rade pkg lint {}`", name);
+}
+
+fn scaffold(dir: &Path, name: &str, repository: &str) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(dir.join(".comrade"))?;
+    let package = PackageInfo {
+        dependencies: Vec::new(),
+        language: "sh".to_string(),
+        repository: repository.to_string(),
+        capacity: 0,
+        version: "0.1.0".to_string(),
+        download: false,
+        description: None,
+        targets: None,
+        env: None,
+        build_requires: None,
+        requires_commands: None,
+    };
+    fs::write(dir.join("package.toml"), toml::to_string(&package)?)?;
+    fs::write(
+        dir.join("install.sh"),
+        format!(
+            "#!/bin/sh\n\
+             # Build {name} here and leave the executable `{name}` in this directory.\n\
+             # rade moves it to ~/.comrade/bin/ when this script succeeds.\n\
+             set -e\n"
+        ),
+    )?;
+    fs::write(dir.join(".comrade/exe_name"), format!("{}\n", name))?;
+    Ok(())
+}
+
+/// a repository URL that points at the local disk.
+fn local_path(repository: &str) -> Option<PathBuf> {
+    if let Some(path) = repository.strip_prefix("file://") {
+        return Some(PathBuf::from(path));
+    }
+    if repository.contains("://") || repository.contains('@') {
+        return None;
+    }
+    Some(PathBuf::from(repository))
+}
+
+fn check_repository(repository: &str) -> Option<Finding> {
+    if repository.is_empty() {
+        return Some(error("repository is empty".to_string()));
+    }
+    match local_path(repository) {
+        Some(path) => match Repository::open(&path) {
+            Ok(_) => None,
+            Err(_) if !path.exists() => Some(error(format!(
+                "repository {} does not exist",
+                path.display()
+            ))),
+            Err(_) => Some(error(format!(
+                "repository {} is not a git repository",
+                path.display()
+            ))),
+        },
+        None => {
+            let reachable = Remote::create_detached(repository)
+                .and_then(|mut remote| remote.connect(Direction::Fetch));
+            reachable
+                .err()
+                .map(|e| error(format!("repository {} is not reachable: {}", repository, e)))
+        }
+    }
+}
+
+/// first line of a name file, or a finding if it is empty.
+fn read_name(path: &Path, findings: &mut Vec<Finding>) -> Option<String> {
+    let s = fs::read_to_string(path).ok()?;
+    let name = s.trim();
+    if name.is_empty() {
+        findings.push(error(format!("{} is empty", path.display())));
+        return None;
+    }
+    if name.lines().count() > 1 {
+        findings.push(error(format!(
+            "{} must contain a single name",
+            path.display()
+        )));
+    }
+    Some(name.lines().next().unwrap_or_default().to_string())
+}
+
+/// check the package in `dir`.
+/// `dir` holds `package.toml`, and `install.sh` when it is also the package's repository.
+pub fn check(dir: &Path) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let name = fs::canonicalize(dir)
+        .ok()
+        .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_default();
+    let table: toml::Table = match fs::read_to_string(dir.join("package.toml")) {
+        Ok(s) => match toml::from_str(&s) {
+            Ok(t) => t,
+            Err(e) => return vec![error(format!("package.toml is not valid TOML: {}", e))],
+        },
+        Err(e) => return vec![error(format!("cannot read package.toml: {}", e))],
+    };
+
+    for (field, kind) in FIELDS {
+        match table.get(field) {
+            None => findings.push(error(format!("package.toml: `{}` is missing", field))),
+            Some(value) if value.type_str() != kind => findings.push(error(format!(
+                "package.toml: `{}` must be {} {}, not {}",
+                field,
+                article(kind),
+                kind,
+                value.type_str()
+            ))),
+            Some(_) => {}
+        }
+    }
+    for (field, kind) in OPTIONAL_FIELDS {
+        if let Some(value) = table.get(field).filter(|v| v.type_str() != kind) {
+            findings.push(error(format!(
+                "package.toml: `{}` must be {} {}, not {}",
+                field,
+                article(kind),
+                kind,
+                value.type_str()
+            )));
+        }
+    }
+    if let Some(env) = table.get("env").and_then(|e| e.as_table()) {
+        for (name, value) in env {
+            if !env::is_variable_name(name) {
+                findings.push(error(format!("env: {} is not a valid variable name", name)));
+            }
+            if !value.is_str() {
+                findings.push(error(format!("env: {} must be a string", name)));
+            }
+        }
+    }
+    if let Some(requires) = table.get("build_requires").and_then(|b| b.as_array()) {
+        for spec in requires {
+            match spec.as_str().map(toolchain::Requirement::parse) {
+                None => findings.push(error(
+                    "build_requires: every entry must be a string".to_string(),
+                )),
+                Some(Err(e)) => findings.push(error(format!("build_requires: {}", e))),
+                Some(Ok(_)) => {}
+            }
+        }
+    }
+    if let Some(commands) = table.get("requires_commands").and_then(|r| r.as_array()) {
+        for command in commands {
+            match command.as_str() {
+                Some(c) if !c.trim().is_empty() && !c.contains(char::is_whitespace) => {}
+                _ => findings.push(error(format!(
+                    "requires_commands: {} is not a command name",
+                    command
+                ))),
+            }
+        }
+    }
+    for key in table.keys() {
+        if !FIELDS.iter().chain(&OPTIONAL_FIELDS).any(|(f, _)| f == key) {
+            findings.push(warning(format!("package.toml: unknown field `{}`", key)));
+        }
+    }
+
+    if let Some(v) = table.get("version").and_then(|v| v.as_str()) {
+        if semver::Version::parse(v.trim_start_matches('v')).is_err() {
+            findings.push(warning(format!(
+                "version {} is not semver; only `*` or the exact version can match it in a Radefile",
+                v
+            )));
+        }
+    }
+    if let Some(capacity) = table.get("capacity").and_then(|c| c.as_integer()) {
+        if capacity < 0 {
+            findings.push(error("capacity must not be negative".to_string()));
+        }
+    }
+    if let Some(dependencies) = table.get("dependencies").and_then(|d| d.as_array()) {
+        for d in dependencies {
+            match d.as_str() {
+                None => findings.push(error(format!(
+                    "dependencies must be strings, found {}",
+                    d.type_str()
+                ))),
+                Some("") => {
+                    findings.push(warning("dependencies contain an empty name".to_string()))
+                }
+                Some(d) if d == name => findings.push(error(format!("{} depends on itself", name))),
+                Some(d) => {
+                    if index::get(d).is_none() {
+                        findings.push(error(format!(
+                            "dependency {} is not in the package list",
+                            d
+                        )));
+                    }
+                }
+            }
+        }
+    }
+
+    let repository = table.get("repository").and_then(|r| r.as_str());
+    if let Some(repository) = repository {
+        findings.extend(check_repository(repository));
+    }
+
+    // the executable name: `.comrade/exe_name` in the repository for packages built
+    // from source, `exec_name` in the package list entry for downloaded packages
+    let download = table.get("download").and_then(|d| d.as_bool());
+    let source_dir = if dir.join("install.sh").exists() {
+        Some(dir.to_path_buf())
+    } else {
+        repository.and_then(local_path).filter(|p| p.is_dir())
+    };
+    let exe_name = source_dir
+        .as_ref()
+        .and_then(|s| read_name(&s.join(".comrade/exe_name"), &mut findings));
+    let exec_name = read_name(&dir.join("exec_name"), &mut findings);
+    match download {
+        Some(true) if !dir.join("exec_name").exists() => findings.push(error(
+            "downloaded packages need an exec_name file next to package.toml".to_string(),
+        )),
+        Some(false) => {
+            if let Some(source) = &source_dir {
+                if !source.join("install.sh").exists() {
+                    findings.push(error(format!("{} has no install.sh", source.display())));
+                }
+            }
+            if exec_name.is_some() {
+                findings.push(warning(
+                    "exec_name is only used by downloaded packages".to_string(),
+                ));
+            }
+        }
+        _ => {}
+    }
+    if let (Some(exe), Some(exec)) = (&exe_name, &exec_name) {
+        if exe != exec {
+            findings.push(error(format!(
+                ".comrade/exe_name ({}) and exec_name ({}) disagree",
+                exe, exec
+            )));
+        }
+    }
+    findings
+}
+
+/// `rade pkg lint`: report problems with the package in `dir`.
+pub fn lint(dir: &Path) {
+    let findings = check(dir);
+    for f in &findings {
+        match f.severity {
+            Severity::Error => println!("{} {}", "error:".red().bold(), f.message),
+            Severity::Warning => println!("{} {}", "warning:".yellow().bold(), f.message),
+        }
+    }
+    let errors = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .count();
+    let warnings = findings.len() - errors;
+    if errors > 0 {
+        println!(
+            "{} {} errors, {} warnings",
+            ">>>".red().bold(),
+            errors,
+            warnings
+        );
+        std::process::exit(1);
+    }
+    println!(
+        "{} {} looks good ({} warnings)",
+        ">>>".green().bold(),
+        dir.display(),
+        warnings
+    );
+}
+
+/// build the package in `dir` from source and return the directory holding the result
+/// and the name of the executable.
+fn build_from_source(dir: &Path, name: &str) -> Result<(PathBuf, String), Box<dyn Error>> {
+    let work = Package::rade_home()
+        .join("build/")
+        .join(format!("pkg-{}", name));
+    if work.exists() {
+        fs::remove_dir_all(&work)?;
+    }
+    if dir.join("install.sh").exists() {
+        sync::copy_dir(dir, &work)?;
+    } else {
+        let info = Package::read_package_info_from(dir)?;
+        println!("{} {}", ">>>".green().bold(), "Clone package...".bold());
+        Repository::clone(&info.repository, &work)?;
+    }
+    let exe = install::get_program_name(work.display().to_string(), name);
+    println!("{} run install.sh (build start)", ">>>".yellow().bold());
+    let status = process::Command::new("sh")
+        .arg(work.join("install.sh"))
+        .current_dir(&work)
+        .status()?;
+    if !status.success() {
+        return Err("install.sh failed".into());
+    }
+    println!("{} {}", ">>>".cyan().bold(), "build end".bold());
+    if !work.join(&exe).is_file() {
+        return Err(format!("install.sh did not produce the executable {}", exe).into());
+    }
+    Ok((work, exe))
+}
+
+/// write the `.radepkg` zip read by `unpack_package`.
+/// Entries are written in a fixed order with a fixed timestamp,
+/// so the same executable always gives the same archive.
+/// `package.toml` goes along, so a local install knows the version and dependencies.
+fn pack(
+    archive: &Path,
+    executable: &Path,
+    exe: &str,
+    package_toml: &Path,
+) -> Result<(), Box<dyn Error>> {
+    let options = FileOptions::default()
+        .compression_method(CompressionMethod::Deflated)
+        .last_modified_time(DateTime::default());
+    let mut zip = ZipWriter::new(fs::File::create(archive)?);
+    zip.start_file(exe, options.unix_permissions(0o755))?;
+    zip.write_all(&fs::read(executable)?)?;
+    zip.start_file("exec_name", options.unix_permissions(0o644))?;
+    zip.write_all(format!("{}\n", exe).as_bytes())?;
+    // nothing left to do after the executable is in place
+    zip.start_file("install.sh", options.unix_permissions(0o755))?;
+    zip.write_all(b"__install_end__\n")?;
+    zip.start_file("package.toml", options.unix_permissions(0o644))?;
+    zip.write_all(&fs::read(package_toml)?)?;
+    zip.finish()?;
+    Ok(())
+}
+
+/// `rade pkg build`: build the package in `dir` and pack it as
+/// `<output>/<name>-<target>.radepkg`, with its sha256 in `<archive>.sha256`.
+pub fn build(dir: &Path, output: &Path) {
+    let findings = check(dir);
+    let errors: Vec<&Finding> = findings
+        .iter()
+        .filter(|f| f.severity == Severity::Error)
+        .collect();
+    if !errors.is_empty() {
+        for f in errors {
+            eprintln!("{} {}", "error:".red().bold(), f.message);
+        }
+        eprintln!(
+            "{} {}",
+            ">>>".red().bold(),
+            "Fix the problems reported by `rade pkg lint` first.".bold()
+        );
+        process::exit(1);
+    }
+    let name = fs::canonicalize(dir)
+        .ok()
+        .and_then(|d| d.file_name().map(|n| n.to_string_lossy().to_string()))
+        .unwrap_or_default();
+
+    let (work, exe) = match build_from_source(dir, &name) {
+        Ok(built) => built,
+        Err(e) => {
+            eprintln!("{} Failed to build {}", ">>>".red().bold(), name);
+            eprintln!("Error code: {}", e);
+            process::exit(1);
+        }
+    };
+    let archive = output.join(format!("{}-{}.radepkg", name, release::target()));
+    let packed = fs::create_dir_all(output)
+        .map_err(|e| e.into())
+        .and_then(|_| pack(&archive, &work.join(&exe), &exe, &dir.join("package.toml")))
+        .and_then(|_| Ok(journal::sha256_file(&archive)?));
+    let _ = fs::remove_dir_all(&work);
+    let hash = match packed {
+        Ok(hash) => hash,
+        Err(e) => {
+            eprintln!(
+                "{} Failed to write {}",
+                ">>>".red().bold(),
+                archive.display()
+            );
+            eprintln!("Error code: {}", e);
+            process::exit(1);
+        }
+    };
+    let file_name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let sha_file = output.join(format!("{}.sha256", file_name));
+    if let Err(e) = fs::write(&sha_file, format!("{}  {}\n", hash, file_name)) {
+        eprintln!(
+            "{} Failed to write {}",
+            ">>>".red().bold(),
+            sha_file.display()
+        );
+        eprintln!("Error code: {}", e);
+        process::exit(1);
+    }
+    println!(
+        "{} {} {}",
+        ">>>".green().bold(),
+        "Built".bold(),
+        archive.display()
+    );
+    println!("sha256: {}", hash);
+}
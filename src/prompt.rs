@@ -0,0 +1,68 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::config;
+use colored::*;
+use serde::Deserialize;
+use std::{
+    io::{self, IsTerminal, Write},
+    sync::OnceLock,
+};
+
+/// how [y/n] prompts are answered.
+#[derive(Deserialize, Default, Clone, Copy, PartialEq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Assume {
+    /// ask on the terminal
+    #[default]
+    Ask,
+    Yes,
+    No,
+}
+
+static ASSUME: OnceLock<Assume> = OnceLock::new();
+
+/// set the answer given by `--yes`/`--no`. Without one, the config default is used.
+pub fn init(assume: Option<Assume>) {
+    let _ = ASSUME.set(assume.unwrap_or(config::get().assume));
+}
+
+/// # confirm
+/// ask a yes/no question. Every [y/n] prompt of rade goes through here.
+///
+/// With `--yes`/`--no` (or `assume` in the config) the answer is printed and returned without reading stdin.
+/// When stdin is not a terminal, rade refuses to guess and exits.
+/// On a terminal, an empty answer counts as yes.
+pub fn confirm(question: &str) -> bool {
+    let assume = *ASSUME.get_or_init(|| config::get().assume);
+    println!("{}", question);
+    match assume {
+        Assume::Yes => {
+            println!("[y/n] y");
+            true
+        }
+        Assume::No => {
+            println!("[y/n] n");
+            false
+        }
+        Assume::Ask => {
+            if !io::stdin().is_terminal() {
+                eprintln!(
+                    "{} {}",
+                    ">>>".red().bold(),
+                    "stdin is not a terminal, refusing to guess the answer.".bold()
+                );
+                eprintln!("Pass --yes or --no, or set `assume` in ~/.comrade/config.toml");
+                std::process::exit(1);
+            }
+            print!("[y/n] ");
+            io::stdout().flush().unwrap();
+            let mut answer = String::new();
+            match io::stdin().read_line(&mut answer) {
+                Ok(0) | Err(_) => false,
+                Ok(_) => ["y", "yes", ""].contains(&answer.trim().to_lowercase().as_str()),
+            }
+        }
+    }
+}
@@ -0,0 +1,373 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    config,
+    journal::{self, Action},
+    prompt, version,
+};
+use colored::*;
+use ed25519_dalek::{Signature, VerifyingKey};
+use reqwest::blocking::Client;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::{cmp::Ordering, collections::BTreeMap, env, error::Error, fs, io, path::Path};
+
+/// the release manifest, `releases.toml`.
+///
+/// ```toml
+/// [[release]]
+/// version = "0.9.0"
+///
+/// [release.targets.x86_64-unknown-linux-gnu]
+/// url = "https://github.com/rade-package-manager/rade-package-manager/releases/download/0.9.0/rade-x86_64-unknown-linux-gnu"
+/// sha256 = "..."
+/// # ed25519 signature of the binary, hex encoded
+/// signature = "..."
+/// ```
+#[derive(Deserialize)]
+pub struct Manifest {
+    #[serde(default)]
+    pub release: Vec<Release>,
+}
+
+#[derive(Deserialize)]
+pub struct Release {
+    pub version: String,
+    #[serde(default)]
+    pub targets: BTreeMap<String, Artifact>,
+}
+
+/// the binary of one release for one target.
+#[derive(Deserialize)]
+pub struct Artifact {
+    pub url: String,
+    pub sha256: String,
+    pub signature: String,
+}
+
+/// architectures as `(std::env::consts::ARCH, the name target triples use)`.
+/// The target part of release and `.radepkg` file names starts with the second.
+pub const ARCHES: [(&str, &str); 7] = [
+    ("x86_64", "x86_64"),
+    ("aarch64", "aarch64"),
+    ("x86", "i686"),
+    ("arm", "armv7"),
+    ("riscv64", "riscv64gc"),
+    ("s390x", "s390x"),
+    ("loongarch64", "loongarch64"),
+];
+
+/// target triple of this build, as used in the release manifest and in `.radepkg` names.
+pub fn target() -> String {
+    let arch = ARCHES
+        .iter()
+        .find(|(arch, _)| *arch == env::consts::ARCH)
+        .map_or(env::consts::ARCH, |(_, name)| name);
+    if cfg!(target_os = "windows") {
+        format!("{}-pc-windows-gnu", arch)
+    } else if cfg!(target_os = "macos") {
+        format!("{}-apple-darwin", arch)
+    } else {
+        format!("{}-unknown-{}-gnu", arch, env::consts::OS)
+    }
+}
+
+/// get `url`. `file://` URLs are read from disk, which is handy for mirrors.
+fn fetch(url: &str) -> Result<Vec<u8>, Box<dyn Error>> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return Ok(fs::read(path)?);
+    }
+    let response = Client::new().get(url).send()?.error_for_status()?;
+    Ok(response.bytes()?.to_vec())
+}
+
+fn fetch_manifest(url: &str) -> Result<Manifest, Box<dyn Error>> {
+    let body = fetch(url)?;
+    Ok(toml::from_str(std::str::from_utf8(&body)?)?)
+}
+
+/// `field` of `[self_update]`, or report that it has to be set and exit.
+/// There is no default: the manifest and the key it is checked with both come from the user.
+fn required(value: &Option<String>, field: &str) -> String {
+    match value {
+        Some(value) if !value.trim().is_empty() => value.clone(),
+        _ => {
+            eprintln!(
+                "{} {}",
+                ">>>".red().bold(),
+                format!(
+                    "self-update needs `{}` under [self_update] in ~/.comrade/config.toml",
+                    field
+                )
+                .bold()
+            );
+            eprintln!("See the self-update section of the README for how releases are signed.");
+            std::process::exit(1);
+        }
+    }
+}
+
+fn from_hex(s: &str) -> Option<Vec<u8>> {
+    let s = s.trim();
+    if !s.len().is_multiple_of(2) {
+        return None;
+    }
+    (0..s.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(s.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+/// check the downloaded binary against the manifest and the release key.
+fn verify(binary: &[u8], artifact: &Artifact, public_key: &str) -> Result<String, String> {
+    let hash: String = Sha256::digest(binary)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+    if !hash.eq_ignore_ascii_case(artifact.sha256.trim()) {
+        return Err(format!(
+            "sha256 mismatch: expected {}, got {}",
+            artifact.sha256, hash
+        ));
+    }
+    let key: [u8; 32] = from_hex(public_key)
+        .and_then(|k| k.try_into().ok())
+        .ok_or("the release key is not a hex encoded ed25519 key")?;
+    let key = VerifyingKey::from_bytes(&key).map_err(|e| e.to_string())?;
+    let signature: [u8; 64] = from_hex(&artifact.signature)
+        .and_then(|s| s.try_into().ok())
+        .ok_or("the signature in the manifest is not valid hex")?;
+    key.verify_strict(binary, &Signature::from_bytes(&signature))
+        .map_err(|_| "signature does not match the release key".to_string())?;
+    Ok(hash)
+}
+
+/// replace `exe` with `binary`. The new file is written next to it and renamed over it,
+/// so an interrupted update never leaves a half written executable behind.
+fn replace_executable(exe: &Path, binary: &[u8]) -> io::Result<()> {
+    let new = exe.with_extension("new");
+    fs::write(&new, binary)?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        fs::set_permissions(&new, fs::Permissions::from_mode(0o755))?;
+    }
+    // a running executable cannot be overwritten on Windows, but it can be renamed
+    #[cfg(windows)]
+    {
+        let old = exe.with_extension("old");
+        let _ = fs::remove_file(&old);
+        fs::rename(exe, &old)?;
+    }
+    fs::rename(&new, exe)
+}
+
+/// upgrade rade itself from the release manifest.
+/// With `check`, only report whether an update is available.
+/// `to` installs that exact version; otherwise the newest one is used,
+/// and an older published version is only installed with `allow_downgrade`.
+pub fn self_update(current: &str, check: bool, to: Option<String>, allow_downgrade: bool) {
+    println!(
+        "{} {}",
+        ">>>".green().bold(),
+        "Checking for rade updates...".bold()
+    );
+    let manifest = match fetch_manifest(&required(&config::get().self_update.manifest, "manifest"))
+    {
+        Ok(m) => m,
+        Err(e) => {
+            eprintln!(
+                "{} {}",
+                ">>>".red().bold(),
+                "Failed to get the release manifest".bold()
+            );
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+    };
+    let release = match &to {
+        Some(to) => manifest
+            .release
+            .iter()
+            .find(|r| version::compare(&r.version, to) == Ordering::Equal),
+        None => manifest
+            .release
+            .iter()
+            .max_by(|a, b| version::compare(&a.version, &b.version)),
+    };
+    let release = match release {
+        Some(r) => r,
+        None => {
+            eprintln!(
+                "{} {}",
+                ">>>".red().bold(),
+                match &to {
+                    Some(to) => format!("Version {} is not published", to),
+                    None => "No releases are published".to_string(),
+                }
+                .bold()
+            );
+            let published: Vec<&str> = manifest
+                .release
+                .iter()
+                .map(|r| r.version.as_str())
+                .collect();
+            eprintln!("Published versions: {:?}", published);
+            std::process::exit(1);
+        }
+    };
+
+    let order = version::compare(&release.version, current);
+    if check {
+        match order {
+            Ordering::Greater => println!(
+                "{} {} {} {}",
+                ">>>".green().bold(),
+                current,
+                "→".green().bold(),
+                release.version
+            ),
+            _ if to.is_some() && order == Ordering::Less => println!(
+                "{} {} {} {} (downgrade)",
+                ">>>".yellow().bold(),
+                current,
+                "→".yellow().bold(),
+                release.version
+            ),
+            _ => println!("{} comrade is already up-to-date!", ">>>".yellow().bold()),
+        }
+        return;
+    }
+    match order {
+        Ordering::Equal => {
+            println!(
+                "{} comrade {} is already running",
+                ">>>".yellow().bold(),
+                current
+            );
+            return;
+        }
+        Ordering::Less if to.is_none() && !allow_downgrade => {
+            println!("{} comrade is already up-to-date!", ">>>".yellow().bold());
+            println!(
+                "The published version {} is older than {}. Use --allow-downgrade to install it.",
+                release.version, current
+            );
+            return;
+        }
+        Ordering::Greater => println!("{}", "Upgrade is valid!".green().bold()),
+        Ordering::Less => println!("{}", "Downgrade requested".yellow().bold()),
+    }
+    println!("{} {} {}", current, "→".green().bold(), release.version);
+
+    let artifact = match release.targets.get(&target()) {
+        Some(a) => a,
+        None => {
+            eprintln!(
+                "{} {} {} for {}",
+                ">>>".red().bold(),
+                "No release binary of".bold(),
+                release.version,
+                target()
+            );
+            std::process::exit(1);
+        }
+    };
+    let public_key = required(&config::get().self_update.public_key, "public_key");
+    if !prompt::confirm("Want to upgrade your comrade?") {
+        println!("{} Upgrade canceled.", ">>>".cyan().bold());
+        return;
+    }
+
+    let fail = |detail: String| -> ! {
+        let _ = journal::Entry::new(Action::Upgrade, "rade")
+            .before(current)
+            .after(&release.version)
+            .failed(&detail)
+            .record();
+        eprintln!("{} {}", ">>>".red().bold(), detail);
+        eprintln!("comrade was not changed");
+        std::process::exit(1);
+    };
+    println!(
+        "{} {} {}",
+        ">>>".green().bold(),
+        "Downloading".bold(),
+        artifact.url
+    );
+    let binary = fetch(&artifact.url).unwrap_or_else(|e| fail(format!("download failed: {}", e)));
+    let hash = verify(&binary, artifact, &public_key)
+        .unwrap_or_else(|e| fail(format!("verification failed: {}", e)));
+    println!("{} {}", ">>>".green().bold(), "Signature verified".bold());
+    let exe = env::current_exe()
+        .and_then(fs::canonicalize)
+        .unwrap_or_else(|e| fail(format!("cannot find the running executable: {}", e)));
+    if let Err(e) = replace_executable(&exe, &binary) {
+        fail(format!("failed to replace {}: {}", exe.display(), e));
+    }
+    let _ = journal::Entry::new(Action::Upgrade, "rade")
+        .before(current)
+        .after(&release.version)
+        .source(Some(hash))
+        .record();
+    println!("{} {}", ">>>".green().bold(), "All done".bold());
+    println!(
+        "{}",
+        format!("comrade has been upgraded to {}.", release.version).yellow()
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ed25519_dalek::{Signer, SigningKey};
+
+    fn hex(bytes: &[u8]) -> String {
+        bytes.iter().map(|b| format!("{:02x}", b)).collect()
+    }
+
+    /// a binary, a manifest entry for it and the hex public key it is signed with.
+    fn signed(binary: &[u8]) -> (Artifact, String) {
+        let key = SigningKey::from_bytes(&[7; 32]);
+        let artifact = Artifact {
+            url: String::new(),
+            sha256: hex(&Sha256::digest(binary)),
+            signature: hex(&key.sign(binary).to_bytes()),
+        };
+        (artifact, hex(key.verifying_key().as_bytes()))
+    }
+
+    #[test]
+    fn verify_accepts_a_signed_binary() {
+        let (artifact, key) = signed(b"rade");
+        assert_eq!(
+            verify(b"rade", &artifact, &key),
+            Ok(artifact.sha256.clone())
+        );
+    }
+
+    #[test]
+    fn verify_rejects_a_bad_hash() {
+        let (mut artifact, key) = signed(b"rade");
+        artifact.sha256 = hex(&Sha256::digest(b"other"));
+        let err = verify(b"rade", &artifact, &key).unwrap_err();
+        assert!(err.starts_with("sha256 mismatch"), "{}", err);
+    }
+
+    #[test]
+    fn verify_rejects_a_bad_signature() {
+        let (mut artifact, key) = signed(b"rade");
+        let (other, _) = signed(b"other");
+        artifact.signature = other.signature;
+        assert_eq!(
+            verify(b"rade", &artifact, &key),
+            Err("signature does not match the release key".to_string())
+        );
+        // signed correctly, but with another key
+        let (artifact, _) = signed(b"rade");
+        let stranger = hex(SigningKey::from_bytes(&[9; 32]).verifying_key().as_bytes());
+        assert!(verify(b"rade", &artifact, &stranger).is_err());
+    }
+}
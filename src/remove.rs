@@ -2,13 +2,14 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
-use crate::{log, logparser, Package};
+use crate::{
+    journal::{self, Action},
+    log::{self, Reason},
+    logparser, output, prompt, Package,
+};
 use colored::*;
 use dirs::home_dir;
-use std::{
-    fs,
-    io::{self, Write},
-};
+use std::fs;
 
 impl Package {
     /// # remove
@@ -27,10 +28,12 @@ impl Package {
                 .expect("Failed to get home dir")
                 .join(".comrade/");
 
-            let (name, version, repo) = Package::log_parse(package);
+            let (name, version, repo) = match Package::log_parse(package) {
+                Ok(log) => log,
+                Err(e) => output::fail("log_invalid", &e.to_string()),
+            };
             let bytes = format!("{}{}", base.join("bin/").display(), &name);
             let bytes = fs::metadata(bytes).expect("Failed to get metadata").len();
-            let mut _str = String::new();
             if !source {
                 println!("\n{}{}", "remove package: ".bold(), &package);
                 println!("{}{}bytes", "Capacity released: ".bold(), bytes);
@@ -41,12 +44,7 @@ impl Package {
                 );
                 println!("{}{}", "version: ".bold(), version);
                 println!("Repository: {}\n", repo.as_str().bold());
-                println!("Do you really want to delete {}?", &package);
-                print!("[y/n] ");
-                io::stdout().flush().unwrap();
-                io::stdin().read_line(&mut _str).unwrap();
-                let _str = _str.trim();
-                if ["y", "yes", ""].contains(&_str) {
+                if prompt::confirm(&format!("Do you really want to delete {}?", &package)) {
                     println!("{} remove executable file...", ">>>".green().bold());
                     if let Err(r) =
                         fs::remove_file(format!("{}{}", base.join("bin/").display(), &name))
@@ -58,6 +56,10 @@ impl Package {
                     }
                     println!("{} remove log file...", ">>>".green().bold());
                     log::Name::new(&base.join("log/install/")).remove_program(package);
+                    let _ = fs::remove_dir_all(base.join("versions/").join(package));
+                    let _ = journal::Entry::new(Action::Remove, package)
+                        .before(&version)
+                        .record();
                 }
             } else {
                 println!("{} remove executable file...", ">>>".green().bold());
@@ -70,7 +72,121 @@ impl Package {
                 }
                 println!("{} remove log file...", ">>>".green().bold());
                 log::Name::new(&base.join("log/install/")).remove_program(package);
+                let _ = fs::remove_dir_all(base.join("versions/").join(package));
+                let _ = journal::Entry::new(Action::Remove, package)
+                    .before(&version)
+                    .record();
+            }
+        }
+    }
+
+    /// # remove_checked
+    /// `rade remove`: refuse to remove a package other installed packages depend on.
+    /// With `cascade` those dependents (and theirs) are removed as well;
+    /// with `force` the package is removed anyway.
+    pub fn remove_checked(package: &str, cascade: bool, force: bool) {
+        let dependents = logparser::dependents(package);
+        if dependents.is_empty() || force {
+            Package::remove(package, false);
+            return;
+        }
+        if !cascade {
+            eprintln!(
+                "{} {} {}",
+                ">>>".red().bold(),
+                package.bold(),
+                "is required by other installed packages:".bold()
+            );
+            for d in &dependents {
+                eprintln!("  {}", d);
             }
+            eprintln!("Use --cascade to remove them too, or --force to remove it anyway.");
+            std::process::exit(1);
+        }
+
+        // every package that depends on `package`, directly or not
+        let mut cascade: Vec<String> = Vec::new();
+        let mut queue = dependents;
+        while let Some(d) = queue.pop() {
+            if d != package && !cascade.contains(&d) {
+                queue.extend(logparser::dependents(&d));
+                cascade.push(d);
+            }
+        }
+        println!(
+            "{} {} {}",
+            ">>>".yellow().bold(),
+            "These packages depend on".bold(),
+            package.bold()
+        );
+        for d in &cascade {
+            println!("  {}", d);
+        }
+        if !prompt::confirm(&format!(
+            "Do you really want to delete {} and {} dependent packages?",
+            package,
+            cascade.len()
+        )) {
+            return;
+        }
+        // a package goes only after the packages of the cascade that depend on it
+        let mut remaining = cascade;
+        while !remaining.is_empty() {
+            let next = remaining
+                .iter()
+                .position(|d| {
+                    !logparser::dependents(d)
+                        .iter()
+                        .any(|o| remaining.contains(o))
+                })
+                // a dependency cycle: nothing is free, so take the first one
+                .unwrap_or(0);
+            let d = remaining.remove(next);
+            Package::remove(&d, true);
+        }
+        Package::remove(package, true);
+    }
+
+    /// # autoremove
+    /// remove packages installed as dependencies that no installed package requires any more.
+    pub fn autoremove() {
+        let mut records = logparser::install_records();
+        let mut orphans: Vec<String> = Vec::new();
+        // removing an orphan can orphan its own dependencies
+        loop {
+            let found: Vec<String> = records
+                .iter()
+                .filter(|r| r.reason == Reason::Dependency)
+                .filter(|r| {
+                    !records
+                        .iter()
+                        .any(|o| o.package != r.package && o.dependencies.contains(&r.package))
+                })
+                .map(|r| r.package.clone())
+                .collect();
+            if found.is_empty() {
+                break;
+            }
+            records.retain(|r| !found.contains(&r.package));
+            orphans.extend(found);
+        }
+        if orphans.is_empty() {
+            println!("{} Nothing to remove.", ">>>".yellow().bold());
+            return;
+        }
+        println!(
+            "{} {}",
+            ">>>".green().bold(),
+            "These dependencies are no longer required:".bold()
+        );
+        for o in &orphans {
+            println!("  {}", o);
+        }
+        if !prompt::confirm(&format!("Remove {} packages?", orphans.len())) {
+            return;
+        }
+        for o in &orphans {
+            Package::remove(o, true);
         }
     }
 }
@@ -2,51 +2,84 @@
 // Copyright (c) 2024 17do
 // This software is licensed under the MIT License.
 
+use crate::{index, output, Package};
 use colored::*;
-use std::{ffi::OsStr, fs};
+use serde::Serialize;
 
-// search package list
-pub fn search_program(program: &str) -> bool {
-    let dir_path = dirs::home_dir()
-        .expect("Failed to get home directory")
-        .join(".comrade/packagelist");
-
-    let dir = match fs::read_dir(&dir_path) {
-        Ok(dir) => dir,
-        Err(e) => {
-            eprintln!(
-                "{}{}{}{}{}Error code:{}",
-                ">>> ".red().bold(),
-                "Failed to retrieve package list.\n".bold(),
-                "please run ".bold(),
-                "rade update ".cyan(),
-                "to retrieve package list.".bold(),
-                e
-            );
-            return false;
-        }
-    };
+#[derive(Serialize)]
+struct SearchResult {
+    name: String,
+    version: Option<String>,
+    description: Option<String>,
+}
 
-    let mut found: bool = false;
-    let mut ret: bool = false;
-    for entry in dir.flatten() {
-        if entry.file_name() == <&str as AsRef<OsStr>>::as_ref(&program) {
-            found = true;
-            let target = entry.path();
-            if target.is_dir() {
-                ret = true;
-            } else {
-                found = false;
-            }
+#[derive(Serialize)]
+struct Search<'a> {
+    query: &'a str,
+    results: Vec<SearchResult>,
+}
 
-            break;
-        }
+// search package list
+pub fn search_program(program: &str) -> bool {
+    if !Package::rade_packagelist().is_dir() {
+        eprintln!(
+            "{}{}{}{}{}",
+            ">>> ".red().bold(),
+            "Failed to retrieve package list.\n".bold(),
+            "please run ".bold(),
+            "rade update ".cyan(),
+            "to retrieve package list.".bold()
+        );
+        return false;
     }
-
-    if !found {
+    if index::get(program).is_none() {
         println!("Program not found: {}", program);
-        ret = false;
+        return false;
+    }
+    true
+}
+
+/// `rade search`: packages in the package list whose name or description contains `query`
+/// (case-insensitive).
+pub fn search(query: &str) {
+    if !Package::rade_packagelist().is_dir() {
+        output::fail(
+            "packagelist_missing",
+            "Failed to retrieve package list. Please run `rade update` to retrieve package list",
+        );
     }
+    let needle = query.to_lowercase();
+    let results: Vec<SearchResult> = index::entries()
+        .into_iter()
+        .filter(|(name, entry)| {
+            name.to_lowercase().contains(&needle)
+                || entry
+                    .description
+                    .as_ref()
+                    .is_some_and(|d| d.to_lowercase().contains(&needle))
+        })
+        .map(|(name, entry)| SearchResult {
+            name,
+            version: Some(entry.version),
+            description: entry.description,
+        })
+        .collect();
 
-    ret
+    if output::is_json() {
+        output::print_json(&Search { query, results });
+        return;
+    }
+    if results.is_empty() {
+        println!("No packages found for {}", query.bold());
+    }
+    for result in results {
+        println!(
+            "{} {}",
+            result.name.bold(),
+            result.version.unwrap_or_default().green()
+        );
+        if let Some(description) = result.description {
+            println!("    {}", description);
+        }
+    }
 }
@@ -0,0 +1,246 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    buildlog, download_install, install,
+    journal::{self, Action},
+    log::{self, Reason},
+    prompt, sync, toolchain, Package,
+};
+use colored::*;
+use git2::Repository;
+use std::{
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+};
+
+/// where `rade install` gets a package that is not taken from the package list.
+pub enum Source {
+    /// a directory on this machine: `./pkg`, `/abs/pkg` or `file:///abs/pkg`
+    Path(PathBuf),
+    /// `git+<url>#<rev>`
+    Git { url: String, rev: Option<String> },
+    /// a binary package: `./<name>-<target>.radepkg`
+    Archive(PathBuf),
+}
+
+impl Source {
+    /// `None` for a plain package name.
+    pub fn parse(spec: &str) -> Option<Source> {
+        if let Some(git) = spec.strip_prefix("git+") {
+            let (url, rev) = match git.split_once('#') {
+                Some((url, rev)) => (url, Some(rev.to_string())),
+                None => (git, None),
+            };
+            return Some(Source::Git {
+                url: url.to_string(),
+                rev,
+            });
+        }
+        let path = match spec.strip_prefix("file://") {
+            Some(path) => path,
+            // package names never contain a path separator
+            None if spec.contains('/') || spec.contains('\\') || spec == "." || spec == ".." => {
+                spec
+            }
+            None if spec.ends_with(".radepkg") => spec,
+            None => return None,
+        };
+        if path.ends_with(".radepkg") {
+            Some(Source::Archive(PathBuf::from(path)))
+        } else {
+            Some(Source::Path(PathBuf::from(path)))
+        }
+    }
+
+    /// the package name: the directory or repository name, or the name part of an archive.
+    fn name(&self) -> Option<String> {
+        match self {
+            Source::Archive(path) => download_install::archive_name(path).map(|(name, _)| name),
+            Source::Path(path) => fs::canonicalize(path)
+                .ok()?
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string()),
+            Source::Git { url, .. } => url
+                .trim_end_matches('/')
+                .rsplit(['/', ':'])
+                .next()
+                .map(|n| n.trim_end_matches(".git").to_string())
+                .filter(|n| !n.is_empty()),
+        }
+    }
+
+    /// how the source is written in the install log.
+    fn location(&self) -> String {
+        match self {
+            Source::Path(path) | Source::Archive(path) => fs::canonicalize(path)
+                .map(|p| format!("file://{}", p.display()))
+                .unwrap_or_else(|_| path.display().to_string()),
+            Source::Git { url, rev } => match rev {
+                Some(rev) => format!("git+{}#{}", url, rev),
+                None => format!("git+{}", url),
+            },
+        }
+    }
+
+    /// put the sources in `build` and return the commit they are at, if known.
+    fn fetch(&self, build: &Path) -> Result<Option<String>, Box<dyn Error>> {
+        match self {
+            Source::Path(path) => {
+                if !path.join("install.sh").exists() {
+                    return Err(format!("{} has no install.sh", path.display()).into());
+                }
+                sync::copy_dir(path, build)?;
+                Ok(Repository::open(path)
+                    .ok()
+                    .and_then(|repo| journal::head_commit(&repo)))
+            }
+            Source::Archive(path) => Err(format!("{} is not a source tree", path.display()).into()),
+            Source::Git { url, rev } => {
+                let repo = Repository::clone(url, build)?;
+                if let Some(rev) = rev {
+                    install::checkout(&repo, rev)?;
+                }
+                Ok(journal::head_commit(&repo))
+            }
+        }
+    }
+}
+
+impl Package {
+    /// ## install_from_source
+    ///
+    /// install a package from a local directory or a git repository instead of the package list.
+    /// `package.toml`, `install.sh` and `.comrade/exe_name` are read from the source itself.
+    pub fn install_from_source(spec: &str) -> Result<(), Box<dyn Error>> {
+        let source = Source::parse(spec).ok_or("not a path or git URL")?;
+        if let Source::Archive(path) = &source {
+            if !path.is_file() {
+                eprintln!("{} {} {}", ">>>".red().bold(), "Not found:".bold(), spec);
+                return Err(format!("cannot find {}", spec).into());
+            }
+            return Package::install_archive(path);
+        }
+        let name = match source.name() {
+            Some(name) => name,
+            None => {
+                eprintln!("{} {} {}", ">>>".red().bold(), "Not found:".bold(), spec);
+                return Err(format!("cannot find {}", spec).into());
+            }
+        };
+        let home = Package::rade_home();
+        let build = home.join("build/").join(&name);
+        if build.exists() {
+            fs::remove_dir_all(&build)?;
+        }
+        let location = source.location();
+        println!(
+            "{} {} {}",
+            ">>>".green().bold(),
+            "Fetching".bold(),
+            location
+        );
+        let commit = match source.fetch(&build) {
+            Ok(commit) => commit,
+            Err(e) => {
+                let _ = journal::Entry::new(Action::Install, &name)
+                    .source(Some(location.clone()))
+                    .failed(&format!("failed to fetch sources: {}", e))
+                    .record();
+                let _ = fs::remove_dir_all(&build);
+                eprintln!("{} Failed to fetch {}", ">>>".red().bold(), location);
+                eprintln!("Error code: {}", e);
+                return Err(e);
+            }
+        };
+        let info = match Package::read_package_info_from(&build) {
+            Ok(info) => info,
+            Err(e) => {
+                let _ = fs::remove_dir_all(&build);
+                eprintln!(
+                    "{} {} has no valid package.toml",
+                    ">>>".red().bold(),
+                    location
+                );
+                eprintln!("Error code: {}", e);
+                return Err(e);
+            }
+        };
+        // the sources have to be fetched to read package.toml, but nothing is built yet
+        let checked =
+            toolchain::check_commands(&name, info.requires_commands.as_deref().unwrap_or_default())
+                .and_then(|()| {
+                    toolchain::check(&name, info.build_requires.as_deref().unwrap_or_default())
+                });
+        if let Err(e) = checked {
+            let _ = journal::Entry::new(Action::Install, &name)
+                .after(&info.version)
+                .source(commit)
+                .failed(&e)
+                .record();
+            let _ = fs::remove_dir_all(&build);
+            return Err(e.into());
+        }
+        let exe = install::get_program_name(build.display().to_string(), &name);
+        let dependencies: Vec<String> = info
+            .dependencies
+            .into_iter()
+            .filter(|d| !d.is_empty())
+            .collect();
+
+        println!("{} {}", "install package:".bold(), name);
+        println!("{} {}", "executable file name:".bold(), exe);
+        println!("{} {}", "language:".bold(), info.language);
+        println!("{} {}", "versions:".bold(), info.version);
+        println!("{} {:?}", "dependencies:".bold(), dependencies);
+        println!("{} {}", "source:".bold(), location);
+        if !prompt::confirm(&format!("\n{} {}?", "install".bold(), name)) {
+            fs::remove_dir_all(&build)?;
+            return Ok(());
+        }
+
+        if !dependencies.is_empty() {
+            println!("Installing Dependencies");
+            for d in &dependencies {
+                if let Err(e) = Package::install_for_dependence(d, true) {
+                    buildlog::discard(&name, &build);
+                    return Err(e);
+                }
+            }
+        }
+        println!("{} run install.sh (build start)", ">>>".yellow().bold());
+        if let Err(e) = buildlog::run(&name, &build) {
+            let _ = journal::Entry::new(Action::Build, &name)
+                .after(&info.version)
+                .source(commit)
+                .failed(&e.to_string())
+                .record();
+            buildlog::discard(&name, &build);
+            return Err(e);
+        }
+        println!("{} {}", ">>>".cyan().bold(), "build end".bold());
+        let bin = home.join("bin/").join(&exe);
+        if bin.is_symlink() || bin.exists() {
+            fs::remove_file(&bin)?;
+        }
+        fs::rename(build.join(&exe), &bin)?;
+        fs::remove_dir_all(&build)?;
+
+        Package::record_install(
+            &name,
+            &exe,
+            &location,
+            &info.version,
+            log::Name::new(&home.join("log/install/"))
+                .dependencies(&dependencies)
+                .reason(Reason::Explicit)
+                .source(commit.clone()),
+            journal::Entry::new(Action::Install, &name).source(commit.or(Some(location.clone()))),
+            true,
+        );
+        println!("{}", "All done!".bold());
+        Ok(())
+    }
+}
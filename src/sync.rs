@@ -0,0 +1,431 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    index,
+    journal::{self, Action},
+    log::Reason,
+    logparser, prompt, version, Package,
+};
+use colored::*;
+use git2::Repository;
+use serde::Deserialize;
+use std::{
+    collections::BTreeMap,
+    env,
+    error::Error,
+    fs,
+    path::{Path, PathBuf},
+    process,
+};
+
+/// `Radefile.toml`: the packages a machine should have.
+///
+/// ```toml
+/// [registries]
+/// company = "https://github.com/company/rade-package-list"
+///
+/// [packages]
+/// hello = "^1.0"
+/// tool = { version = ">=0.2, <0.4", registry = "company" }
+/// ```
+///
+/// A registry is a package list repository, cloned to `~/.comrade/registries/<name>`.
+#[derive(Deserialize)]
+pub struct Radefile {
+    #[serde(default)]
+    pub registries: BTreeMap<String, String>,
+    #[serde(default)]
+    pub packages: BTreeMap<String, Requirement>,
+}
+
+/// a package entry: a version constraint, or a table with `version` and `registry`.
+#[derive(Deserialize)]
+#[serde(untagged)]
+pub enum Requirement {
+    Version(String),
+    Detailed {
+        #[serde(default)]
+        version: Option<String>,
+        #[serde(default)]
+        registry: Option<String>,
+    },
+}
+
+impl Requirement {
+    fn version(&self) -> &str {
+        match self {
+            Requirement::Version(v) => v,
+            Requirement::Detailed { version, .. } => version.as_deref().unwrap_or("*"),
+        }
+    }
+    fn registry(&self) -> Option<&str> {
+        match self {
+            Requirement::Version(_) => None,
+            Requirement::Detailed { registry, .. } => registry.as_deref(),
+        }
+    }
+}
+
+/// one change `rade sync` would make.
+enum Step {
+    Install {
+        name: String,
+        version: String,
+        registry: Option<String>,
+    },
+    Change {
+        name: String,
+        from: String,
+        to: String,
+        registry: Option<String>,
+    },
+    Use {
+        name: String,
+        from: String,
+        to: String,
+    },
+    Remove {
+        name: String,
+        version: String,
+    },
+    Unsatisfiable {
+        name: String,
+        req: String,
+        reason: String,
+    },
+}
+
+/// read a Radefile.
+pub fn read(path: &Path) -> Result<Radefile, Box<dyn Error>> {
+    Ok(toml::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// directory holding the package list entry of `package`.
+fn package_dir(package: &str, registry: Option<&str>, registries: &Path) -> PathBuf {
+    match registry {
+        Some(r) => registries.join(r).join(package),
+        None => Package::rade_packagelist().join(package),
+    }
+}
+
+/// version of `package` in its package list, or `None` if it is not listed there.
+fn available(package: &str, registry: Option<&str>, registries: &Path) -> Option<String> {
+    if registry.is_none() {
+        return index::get(package).map(|entry| entry.version);
+    }
+    let s =
+        fs::read_to_string(package_dir(package, registry, registries).join("package.toml")).ok()?;
+    let info: toml::Table = toml::from_str(&s).ok()?;
+    Some(info.get("version")?.as_str()?.to_string())
+}
+
+/// clone every registry of the Radefile to `registries`.
+/// The clones are only recorded in the journal when they replace the ones in `~/.comrade/registries/`.
+fn fetch_registries(radefile: &Radefile, registries: &Path) -> Result<(), Box<dyn Error>> {
+    let record = registries == Package::rade_home().join("registries/");
+    for (name, url) in &radefile.registries {
+        let path = registries.join(name);
+        println!(
+            "{} {} {}",
+            ">>>".green().bold(),
+            "Fetching registry".bold(),
+            name.bold()
+        );
+        if path.exists() {
+            fs::remove_dir_all(&path)?;
+        }
+        let entry = journal::Entry::new(Action::Update, &format!("registry:{}", name));
+        match Repository::clone(url, &path) {
+            Ok(repo) => {
+                if record {
+                    let _ = entry.source(journal::head_commit(&repo)).record();
+                }
+            }
+            Err(e) => {
+                if record {
+                    let _ = entry.failed(&e.to_string()).record();
+                }
+                return Err(format!("failed to clone registry {} ({}): {}", name, url, e).into());
+            }
+        }
+    }
+    Ok(())
+}
+
+/// compare the Radefile with what is installed.
+fn plan(radefile: &Radefile, prune: bool, registries: &Path) -> Vec<Step> {
+    let mut steps = Vec::new();
+    for (name, req) in &radefile.packages {
+        let constraint = req.version();
+        let registry = req.registry().map(|r| r.to_string());
+        if let Some(r) = &registry {
+            if !radefile.registries.contains_key(r) {
+                steps.push(Step::Unsatisfiable {
+                    name: name.clone(),
+                    req: constraint.to_string(),
+                    reason: format!("registry {} is not declared in [registries]", r),
+                });
+                continue;
+            }
+        }
+        let installed = Package::installed_version(name);
+        if installed
+            .as_ref()
+            .is_some_and(|v| version::satisfies(v, constraint))
+        {
+            continue;
+        }
+        let candidate = available(name, registry.as_deref(), registries);
+        let candidate_ok = candidate
+            .as_ref()
+            .is_some_and(|v| version::satisfies(v, constraint));
+        // a version installed side by side is preferred over building again
+        let retained = installed.as_ref().and_then(|_| {
+            Package::retained_versions(name)
+                .into_iter()
+                .map(|(v, _)| v)
+                .find(|v| version::satisfies(v, constraint))
+        });
+        match (installed, retained, candidate) {
+            (Some(from), Some(to), _) => steps.push(Step::Use {
+                name: name.clone(),
+                from,
+                to,
+            }),
+            (None, _, Some(version)) if candidate_ok => steps.push(Step::Install {
+                name: name.clone(),
+                version,
+                registry,
+            }),
+            (Some(from), None, Some(to)) if candidate_ok => steps.push(Step::Change {
+                name: name.clone(),
+                from,
+                to,
+                registry,
+            }),
+            (_, _, candidate) => steps.push(Step::Unsatisfiable {
+                name: name.clone(),
+                req: constraint.to_string(),
+                reason: match candidate {
+                    Some(v) => format!("the package list has {}", v),
+                    None => "not in the package list".to_string(),
+                },
+            }),
+        }
+    }
+
+    if prune {
+        let records = logparser::install_records();
+        for r in &records {
+            if r.reason != Reason::Explicit || radefile.packages.contains_key(&r.package) {
+                continue;
+            }
+            // still needed by a package that stays installed
+            let needed = records.iter().any(|o| {
+                o.package != r.package
+                    && o.dependencies.contains(&r.package)
+                    && radefile.packages.contains_key(&o.package)
+            });
+            if !needed {
+                steps.push(Step::Remove {
+                    name: r.package.clone(),
+                    version: r.version.clone(),
+                });
+            }
+        }
+    }
+    steps
+}
+
+fn print_plan(steps: &[Step]) {
+    for step in steps {
+        match step {
+            Step::Install { name, version, .. } => {
+                println!("{} {} {}", "+".green().bold(), name.bold(), version)
+            }
+            Step::Change { name, from, to, .. } => {
+                let sign = if version::is_newer(to, from) {
+                    "↑".green().bold()
+                } else {
+                    "↓".yellow().bold()
+                };
+                println!("{} {} {} → {}", sign, name.bold(), from, to)
+            }
+            Step::Use { name, from, to } => println!(
+                "{} {} {} → {} (already installed)",
+                "~".cyan().bold(),
+                name.bold(),
+                from,
+                to
+            ),
+            Step::Remove { name, version } => {
+                println!("{} {} {}", "-".red().bold(), name.bold(), version)
+            }
+            Step::Unsatisfiable { name, req, reason } => {
+                println!("{} {} {}: {}", "!".red().bold(), name.bold(), req, reason)
+            }
+        }
+    }
+}
+
+/// have the install code take `name` from its registry, if it has one.
+fn stage(name: &str, registry: &Option<String>) -> Result<(), Box<dyn Error>> {
+    if let Some(r) = registry {
+        index::use_registry(name, r)?;
+    }
+    Ok(())
+}
+
+/// copy a directory tree.
+pub fn copy_dir(from: &Path, to: &Path) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(to)?;
+    for entry in fs::read_dir(from)? {
+        let entry = entry?;
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &to.join(entry.file_name()))?;
+        } else {
+            fs::copy(entry.path(), to.join(entry.file_name()))?;
+        }
+    }
+    Ok(())
+}
+
+fn apply(step: &Step) -> Result<(), Box<dyn Error>> {
+    match step {
+        Step::Install { name, registry, .. } => {
+            stage(name, registry)?;
+            Package::install(name, true, false)
+        }
+        Step::Change {
+            name,
+            from,
+            to,
+            registry,
+        } => {
+            stage(name, registry)?;
+            let entry = journal::Entry::new(Action::Upgrade, name)
+                .before(from)
+                .after(to);
+            match Package::install_upgrade(name) {
+                Ok(()) => {
+                    let _ = entry.record();
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = entry.failed(&e.to_string()).record();
+                    Err(e)
+                }
+            }
+        }
+        Step::Use { name, from, to } => {
+            let entry = journal::Entry::new(Action::Use, name)
+                .before(from)
+                .after(to);
+            match Package::use_version(name, to) {
+                Ok(()) => {
+                    let _ = entry.record();
+                    Ok(())
+                }
+                Err(e) => {
+                    let _ = entry.failed(&e.to_string()).record();
+                    Err(e)
+                }
+            }
+        }
+        Step::Remove { name, .. } => {
+            Package::remove(name, true);
+            Ok(())
+        }
+        Step::Unsatisfiable { .. } => Ok(()),
+    }
+}
+
+/// `rade sync`: make the installed packages match a Radefile.
+/// The planned changes are always shown first; `dry_run` stops there.
+pub fn sync(file: &Path, prune: bool, dry_run: bool) {
+    let radefile = match read(file) {
+        Ok(r) => r,
+        Err(e) => {
+            eprintln!(
+                "{} {} {}",
+                ">>>".red().bold(),
+                "Failed to read".bold(),
+                file.display()
+            );
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+    };
+    // a dry run plans against fresh clones in a scratch directory and changes nothing
+    let registries = if dry_run {
+        env::temp_dir().join(format!("rade-sync-{}", process::id()))
+    } else {
+        Package::rade_home().join("registries/")
+    };
+    if let Err(e) = fetch_registries(&radefile, &registries) {
+        if dry_run {
+            let _ = fs::remove_dir_all(&registries);
+        }
+        eprintln!("{} {}", ">>>".red().bold(), e);
+        std::process::exit(1);
+    }
+
+    let steps = plan(&radefile, prune, &registries);
+    if dry_run {
+        let _ = fs::remove_dir_all(&registries);
+    }
+    let unsatisfiable = steps
+        .iter()
+        .filter(|s| matches!(s, Step::Unsatisfiable { .. }))
+        .count();
+    if steps.len() == unsatisfiable {
+        print_plan(&steps);
+        if unsatisfiable == 0 {
+            println!("{} Everything is in sync.", ">>>".yellow().bold());
+            return;
+        }
+        std::process::exit(1);
+    }
+    println!("{}", "Planned changes:".bold());
+    print_plan(&steps);
+    if dry_run {
+        return;
+    }
+    if !prompt::confirm(&format!("\nApply {} changes?", steps.len() - unsatisfiable)) {
+        println!("{} Sync canceled.", ">>>".cyan().bold());
+        return;
+    }
+
+    let mut failed: Vec<String> = Vec::new();
+    for step in &steps {
+        if let Err(e) = apply(step) {
+            let name = match step {
+                Step::Install { name, .. }
+                | Step::Change { name, .. }
+                | Step::Use { name, .. }
+                | Step::Remove { name, .. }
+                | Step::Unsatisfiable { name, .. } => name,
+            };
+            eprintln!(
+                "{} Failed to sync {}: {}. Skipping.",
+                ">>>".red().bold(),
+                name,
+                e
+            );
+            failed.push(name.clone());
+        }
+    }
+    println!();
+    println!(
+        "{} {} applied, {} failed, {} unsatisfiable",
+        ">>>".green().bold(),
+        steps.len() - unsatisfiable - failed.len(),
+        failed.len(),
+        unsatisfiable
+    );
+    if !failed.is_empty() || unsatisfiable > 0 {
+        std::process::exit(1);
+    }
+}
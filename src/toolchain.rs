@@ -0,0 +1,266 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{version, which};
+use colored::*;
+use std::{
+    io,
+    path::Path,
+    process::{Command, Stdio},
+    thread,
+    time::{Duration, Instant},
+};
+
+/// how long a tool may take to print its version.
+const VERSION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// a command a package needs to build, from `build_requires` in `package.toml`:
+/// `"cmake"`, `"cargo >= 1.70"` or `"go >=1.21, <2"`.
+pub struct Requirement {
+    pub command: String,
+    /// a Cargo style version constraint, as accepted by `version::satisfies`
+    pub version: Option<String>,
+}
+
+impl Requirement {
+    pub fn parse(spec: &str) -> Result<Requirement, String> {
+        let spec = spec.trim();
+        let split = spec
+            .find(|c: char| c.is_whitespace() || "<>=~^".contains(c))
+            .unwrap_or(spec.len());
+        let (command, constraint) = spec.split_at(split);
+        if command.is_empty() {
+            return Err(format!("`{}` does not start with a command name", spec));
+        }
+        let constraint = constraint.trim();
+        if !constraint.is_empty() && semver::VersionReq::parse(constraint).is_err() {
+            return Err(format!(
+                "`{}` is not a version constraint like `>= 1.70`",
+                constraint
+            ));
+        }
+        Ok(Requirement {
+            command: command.to_string(),
+            version: Some(constraint.to_string()).filter(|c| !c.is_empty()),
+        })
+    }
+}
+
+/// why a requirement is not met.
+enum Unmet {
+    Missing,
+    TooOld(String),
+}
+
+/// where to get the usual toolchains.
+fn hint(command: &str) -> &'static str {
+    match command {
+        "cargo" | "rustc" => "install or update it with rustup: https://rustup.rs",
+        "go" => "download it from https://go.dev/dl",
+        "node" | "npm" => "download it from https://nodejs.org",
+        "python" | "python3" | "pip" | "pip3" => "download it from https://python.org",
+        _ => "install it with your system package manager",
+    }
+}
+
+/// the first `1.2` or `1.2.3` like word in `text`, cut to major.minor.patch so it can be
+/// compared with semver constraints: `go1.22.1` gives `1.22.1`, `1.2.3.4` gives `1.2.3`.
+fn find_version(text: &str) -> Option<String> {
+    let bytes = text.as_bytes();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i].is_ascii_digit() {
+            let end = text[i..]
+                .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+                .map_or(text.len(), |e| i + e);
+            let candidate = text[i..end].trim_end_matches('.');
+            if candidate.contains('.') {
+                let parts: Vec<&str> = candidate.split('.').take(3).collect();
+                return Some(parts.join("."));
+            }
+            i = end;
+        } else {
+            i += 1;
+        }
+    }
+    None
+}
+
+/// the version the command at `path` reports, `Ok(None)` if it does not print one in time.
+fn installed_version(command: &str, path: &Path) -> io::Result<Option<String>> {
+    // `go` has no --version; other commands are not guessed at, since
+    // `make version` would try to build a target
+    let arg = if command == "go" {
+        "version"
+    } else {
+        "--version"
+    };
+    let mut child = Command::new(path)
+        .arg(arg)
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let started = Instant::now();
+    while child.try_wait()?.is_none() {
+        if started.elapsed() > VERSION_TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            return Ok(None);
+        }
+        thread::sleep(Duration::from_millis(20));
+    }
+    let output = child.wait_with_output()?;
+    let text = format!(
+        "{}\n{}",
+        String::from_utf8_lossy(&output.stdout),
+        String::from_utf8_lossy(&output.stderr)
+    );
+    Ok(find_version(&text))
+}
+
+/// check `build_requires` of `package` before anything is downloaded.
+/// Unmet requirements are listed with what to do about them, and returned as an error.
+pub fn check(package: &str, build_requires: &[String]) -> Result<(), String> {
+    let mut unmet = Vec::new();
+    for spec in build_requires {
+        let requirement = match Requirement::parse(spec) {
+            Ok(r) => r,
+            Err(e) => {
+                eprintln!(
+                    "{} Ignoring build requirement of {}: {}",
+                    ">>>".yellow().bold(),
+                    package,
+                    e
+                );
+                continue;
+            }
+        };
+        let Some(path) = which::find(&requirement.command) else {
+            unmet.push((spec, requirement, Unmet::Missing));
+            continue;
+        };
+        // without a constraint the tool is not run at all
+        let Some(req) = &requirement.version else {
+            continue;
+        };
+        match installed_version(&requirement.command, &path) {
+            Ok(Some(found)) if !version::satisfies(&found, req) => {
+                unmet.push((spec, requirement, Unmet::TooOld(found)))
+            }
+            Ok(Some(_)) => {}
+            Ok(None) => eprintln!(
+                "{} Cannot tell which version of {} is installed; assuming it is recent enough",
+                ">>>".yellow().bold(),
+                requirement.command
+            ),
+            // it exists but would not run for us; install.sh will tell
+            Err(_) => {}
+        }
+    }
+    if unmet.is_empty() {
+        return Ok(());
+    }
+
+    eprintln!(
+        "{} {} {}",
+        ">>>".red().bold(),
+        package.bold(),
+        "cannot be built on this system:".bold()
+    );
+    let width = unmet.iter().map(|(s, _, _)| s.len()).max().unwrap_or(0);
+    for (spec, requirement, why) in &unmet {
+        let why = match why {
+            Unmet::Missing => "not found".to_string(),
+            Unmet::TooOld(found) => format!("found {}", found),
+        };
+        eprintln!(
+            "    {:<width$}  {:<12}  {}",
+            spec,
+            why,
+            hint(&requirement.command),
+            width = width
+        );
+    }
+    eprintln!("Install or update these tools, then run the command again.");
+    let commands: Vec<&str> = unmet.iter().map(|(_, r, _)| r.command.as_str()).collect();
+    Err(format!("missing build tools: {}", commands.join(", ")))
+}
+
+/// check `requires_commands` of `package`: commands it needs at run time that rade does not provide.
+pub fn check_commands(package: &str, commands: &[String]) -> Result<(), String> {
+    let missing: Vec<&str> = commands
+        .iter()
+        .map(|c| c.trim())
+        .filter(|c| which::find(c).is_none())
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    eprintln!(
+        "{} {} {}",
+        ">>>".red().bold(),
+        package.bold(),
+        "needs commands that are not installed:".bold()
+    );
+    let width = missing.iter().map(|c| c.len()).max().unwrap_or(0);
+    for command in &missing {
+        eprintln!("    {:<width$}  {}", command, hint(command), width = width);
+    }
+    eprintln!("rade does not install these; add them to PATH, then run the command again.");
+    Err(format!("missing commands: {}", missing.join(", ")))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_requirements() {
+        let cases = [
+            ("cmake", "cmake", None),
+            ("  make  ", "make", None),
+            ("cargo >= 1.70", "cargo", Some(">= 1.70")),
+            ("cargo>=1.70", "cargo", Some(">=1.70")),
+            ("node ^18", "node", Some("^18")),
+            ("go ~1.21.0", "go", Some("~1.21.0")),
+            ("go >=1.21, <2", "go", Some(">=1.21, <2")),
+            // a bare version means `^`, as in Cargo
+            ("python3 3.10", "python3", Some("3.10")),
+            ("gcc =13.2.0", "gcc", Some("=13.2.0")),
+        ];
+        for (spec, command, version) in cases {
+            let r = Requirement::parse(spec).unwrap_or_else(|e| panic!("{:?}: {}", spec, e));
+            assert_eq!(r.command, command, "{:?}", spec);
+            assert_eq!(r.version.as_deref(), version, "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn parse_rejects_bad_requirements() {
+        for spec in ["", ">= 1.70", "cargo >= new", "cmake 3.x.y.z"] {
+            assert!(Requirement::parse(spec).is_err(), "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn find_version_in_tool_output() {
+        let cases = [
+            (
+                "cmake version 3.27.1\n\nCMake suite maintained by Kitware",
+                Some("3.27.1"),
+            ),
+            ("cargo 1.75.0 (1d8b05cdd 2023-11-20)", Some("1.75.0")),
+            ("go version go1.22.1 linux/amd64", Some("1.22.1")),
+            ("Python 3.12", Some("3.12")),
+            ("git version 2.39.2.windows.1", Some("2.39.2")),
+            ("GNU Make 4.3.\nBuilt for x86_64-pc-linux-gnu", Some("4.3")),
+            ("gcc (GCC) 13 2023", None),
+            ("", None),
+        ];
+        for (output, expected) in cases {
+            assert_eq!(find_version(output).as_deref(), expected, "{:?}", output);
+        }
+    }
+}
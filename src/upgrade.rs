@@ -0,0 +1,205 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    index,
+    journal::{self, Action},
+    output, prompt, version, Package,
+};
+use colored::*;
+use serde::Serialize;
+use std::{cmp::Ordering, error::Error, fs};
+
+#[derive(Serialize)]
+struct OutdatedList<'a> {
+    packages: &'a [Outdated],
+}
+
+/// an installed package with a newer (or, for downgrades, older) version in the package list.
+#[derive(Serialize)]
+pub struct Outdated {
+    pub name: String,
+    pub installed: String,
+    pub available: String,
+}
+
+/// version of `package` in the package list, or `None` if it is not listed.
+/// A package installed from a registry is looked up there, and upgraded from there too.
+pub fn available_version(package: &str) -> Option<String> {
+    match Package::read_install_record(package)
+        .ok()
+        .and_then(|record| record.registry)
+    {
+        Some(registry) => index::use_registry(package, &registry)
+            .ok()
+            .map(|entry| entry.version),
+        None => index::get(package).map(|entry| entry.version),
+    }
+}
+
+/// installed packages with a newer version in the package list, sorted by name.
+/// With `allow_downgrade`, packages whose package list version is older are included too.
+/// An install log that cannot be read is returned as an error.
+pub fn outdated_packages(allow_downgrade: bool) -> Result<Vec<Outdated>, Box<dyn Error>> {
+    let dir = match fs::read_dir(Package::rade_home().join("log/install")) {
+        Ok(dir) => dir,
+        Err(_) => return Ok(Vec::new()),
+    };
+    let mut outdated = Vec::new();
+    for entry in dir.flatten() {
+        let Ok(name) = entry.file_name().into_string() else {
+            continue;
+        };
+        let (_, installed, _) = Package::log_parse(&name)?;
+        let Some(available) = available_version(&name) else {
+            continue;
+        };
+        let order = version::compare(&available, &installed);
+        if order == Ordering::Equal || order == Ordering::Less && !allow_downgrade {
+            continue;
+        }
+        outdated.push(Outdated {
+            name,
+            installed,
+            available,
+        });
+    }
+    outdated.sort_by(|a, b| a.name.cmp(&b.name));
+    Ok(outdated)
+}
+
+/// `outdated_packages`, reporting an unreadable install log and exiting.
+fn outdated_or_fail(allow_downgrade: bool) -> Vec<Outdated> {
+    outdated_packages(allow_downgrade)
+        .unwrap_or_else(|e| output::fail("log_invalid", &e.to_string()))
+}
+
+/// `rade outdated`: show installed vs available versions. Nothing is changed.
+pub fn outdated() {
+    let outdated = outdated_or_fail(false);
+    if output::is_json() {
+        output::print_json(&OutdatedList {
+            packages: &outdated,
+        });
+        return;
+    }
+    if outdated.is_empty() {
+        println!("{} All packages are up-to-date!", ">>>".yellow().bold());
+        return;
+    }
+    for pkg in outdated {
+        println!(
+            "{}: {} → {}",
+            pkg.name.as_str().yellow().bold(),
+            pkg.installed.as_str().blue().bold(),
+            pkg.available.as_str().green().bold()
+        );
+    }
+}
+
+/// `rade upgrade`: upgrade the given packages, or every outdated package with `all`.
+/// A package that fails to upgrade is reported and skipped.
+/// Going back to an older version needs `allow_downgrade`.
+pub fn upgrade(packages: Vec<String>, all: bool, allow_downgrade: bool) {
+    if packages.is_empty() && !all {
+        eprintln!(
+            "{} {}",
+            ">>>".red().bold(),
+            "Specify the packages to upgrade, or --all".bold()
+        );
+        std::process::exit(1);
+    }
+    let outdated = outdated_or_fail(allow_downgrade);
+    let targets: Vec<&Outdated> = if all {
+        outdated.iter().collect()
+    } else {
+        packages
+            .iter()
+            .filter_map(|name| {
+                let found = outdated.iter().find(|o| &o.name == name);
+                if found.is_none() {
+                    match (Package::installed_version(name), available_version(name)) {
+                        (None, _) => {
+                            println!("{} {} is not installed", ">>>".yellow().bold(), name)
+                        }
+                        (Some(installed), Some(available))
+                            if version::is_newer(&installed, &available) =>
+                        {
+                            println!(
+                                "{} {} {} is newer than {} in the package list (use --allow-downgrade)",
+                                ">>>".yellow().bold(),
+                                name,
+                                installed,
+                                available
+                            )
+                        }
+                        _ => println!("{} {} is already up-to-date", ">>>".yellow().bold(), name),
+                    }
+                }
+                found
+            })
+            .collect()
+    };
+    if targets.is_empty() {
+        println!("{} Nothing to upgrade.", ">>>".yellow().bold());
+        return;
+    }
+
+    for pkg in &targets {
+        println!(
+            "{}: {} → {}",
+            pkg.name.as_str().yellow().bold(),
+            pkg.installed.as_str().blue().bold(),
+            pkg.available.as_str().green().bold()
+        );
+    }
+    if !prompt::confirm(&format!(
+        "\n{} {} packages?",
+        "upgrade".bold(),
+        targets.len()
+    )) {
+        println!("{} Upgrade canceled.", ">>>".cyan().bold());
+        return;
+    }
+
+    let mut failed: Vec<&str> = Vec::new();
+    for pkg in &targets {
+        println!(
+            "\n{} {} {}",
+            ">>>".green().bold(),
+            "Upgrading".bold(),
+            pkg.name.as_str().bold()
+        );
+        let entry = journal::Entry::new(Action::Upgrade, &pkg.name)
+            .before(&pkg.installed)
+            .after(&pkg.available);
+        match Package::install_upgrade(&pkg.name) {
+            Ok(()) => {
+                let _ = entry.record();
+            }
+            Err(e) => {
+                let _ = entry.failed(&e.to_string()).record();
+                eprintln!(
+                    "{} Failed to upgrade {}: {}. Skipping.",
+                    ">>>".red().bold(),
+                    pkg.name,
+                    e
+                );
+                failed.push(&pkg.name);
+            }
+        }
+    }
+
+    println!();
+    println!(
+        "{} {} upgraded, {} failed",
+        ">>>".green().bold(),
+        targets.len() - failed.len(),
+        failed.len()
+    );
+    if !failed.is_empty() {
+        eprintln!("Failed: {}", failed.join(", "));
+        std::process::exit(1);
+    }
+}
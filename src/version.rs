@@ -0,0 +1,176 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use semver::{Version, VersionReq};
+use std::cmp::Ordering;
+
+/// # compare
+/// compare two package versions.
+///
+/// A leading `v` is ignored, and missing minor/patch numbers count as 0,
+/// so `v1.0`, `1.0` and `1.0.0` are all equal.
+/// If both versions are then valid semver, semver precedence is used
+/// (`1.0.0-beta` < `1.0.0` < `1.0.1` < `1.10.0`).
+///
+/// Otherwise the versions are split on `.`, `-`, `_` and `+` and compared part by part:
+/// - numeric parts compare as numbers (`2` < `10`)
+/// - other parts compare as text
+/// - a numeric part is newer than a text part (`1.0.beta` < `1.0.1`)
+/// - a version with more parts is newer when all shared parts are equal (`1.0` < `1.0.x`)
+pub fn compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (normalize(a), normalize(b));
+    match (parse(&a), parse(&b)) {
+        (Some(a), Some(b)) => a.cmp_precedence(&b),
+        _ => compare_parts(&a, &b),
+    }
+}
+
+/// `true` if `candidate` is newer than `current`.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    compare(candidate, current) == Ordering::Greater
+}
+
+/// # satisfies
+/// `true` if `version` meets the constraint `req`.
+///
+/// Constraints use Cargo's syntax: `*`, `1.2` (same as `^1.2`), `~1.2.3`, `>=1.0, <2`, `=1.4.0`.
+/// An empty constraint matches everything.
+/// A version (or constraint) that is not semver only matches `*` or the same version, e.g. `=2024.1.beta`.
+pub fn satisfies(version: &str, req: &str) -> bool {
+    let req = req.trim();
+    if req.is_empty() || req == "*" {
+        return true;
+    }
+    match (parse(&normalize(version)), VersionReq::parse(req)) {
+        (Some(v), Ok(r)) => r.matches(&v),
+        _ => compare(version, req.trim_start_matches('=')) == Ordering::Equal,
+    }
+}
+
+fn normalize(version: &str) -> String {
+    let version = version.trim();
+    version
+        .strip_prefix(['v', 'V'])
+        .unwrap_or(version)
+        .to_string()
+}
+
+fn parse(version: &str) -> Option<Version> {
+    if let Ok(v) = Version::parse(version) {
+        return Some(v);
+    }
+    // fill in the missing minor/patch numbers: 1 -> 1.0.0, 1.2-rc -> 1.2.0-rc
+    let split = version.find(['-', '+']).unwrap_or(version.len());
+    let (core, rest) = version.split_at(split);
+    let dots = core.matches('.').count();
+    if dots >= 2 {
+        return None;
+    }
+    let padded = format!("{}{}{}", core, ".0".repeat(2 - dots), rest);
+    Version::parse(&padded).ok()
+}
+
+fn compare_parts(a: &str, b: &str) -> Ordering {
+    let split = |s: &str| -> Vec<String> {
+        s.split(['.', '-', '_', '+'])
+            .filter(|p| !p.is_empty())
+            .map(|p| p.to_string())
+            .collect()
+    };
+    let (a, b) = (split(a), split(b));
+    for (x, y) in a.iter().zip(b.iter()) {
+        let ord = match (x.parse::<u64>(), y.parse::<u64>()) {
+            (Ok(x), Ok(y)) => x.cmp(&y),
+            (Ok(_), Err(_)) => Ordering::Greater,
+            (Err(_), Ok(_)) => Ordering::Less,
+            (Err(_), Err(_)) => x.cmp(y),
+        };
+        if ord != Ordering::Equal {
+            return ord;
+        }
+    }
+    a.len().cmp(&b.len())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn compare_orders_versions() {
+        let cases = [
+            // `v` prefixes are ignored
+            ("v1.0.0", "1.0.0", Ordering::Equal),
+            ("V2.1", "v2.1.0", Ordering::Equal),
+            // missing minor/patch numbers count as 0
+            ("1.0", "1.0.0", Ordering::Equal),
+            ("1", "1.0.0", Ordering::Equal),
+            ("1.2", "1.10", Ordering::Less),
+            ("2.0", "1.9.9", Ordering::Greater),
+            // semver precedence, including pre-releases
+            ("1.0.0-beta", "1.0.0", Ordering::Less),
+            ("1.0.0-alpha", "1.0.0-beta", Ordering::Less),
+            ("1.0.0-rc.2", "1.0.0-rc.10", Ordering::Less),
+            ("1.2-rc", "1.2.0", Ordering::Less),
+            ("1.0.0+build.1", "1.0.0+build.2", Ordering::Equal),
+            ("1.0.1", "1.0.0", Ordering::Greater),
+            // not semver: compared part by part
+            ("1.2.3.4", "1.2.3.10", Ordering::Less),
+            ("2024.1.15", "2024.1.3.1", Ordering::Greater),
+            ("1.0.beta", "1.0.1", Ordering::Less),
+            ("1.0.0.0", "1.0.0", Ordering::Greater),
+            ("2024.1.beta", "2024.1.alpha", Ordering::Greater),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(compare(a, b), expected, "compare({:?}, {:?})", a, b);
+            assert_eq!(
+                compare(b, a),
+                expected.reverse(),
+                "compare({:?}, {:?})",
+                b,
+                a
+            );
+        }
+    }
+
+    #[test]
+    fn is_newer_is_strict() {
+        assert!(is_newer("1.0.1", "v1.0.0"));
+        assert!(!is_newer("1.0", "1.0.0"));
+        assert!(!is_newer("1.0.0-rc.1", "1.0.0"));
+    }
+
+    #[test]
+    fn satisfies_constraints() {
+        let cases = [
+            ("1.5.0", "", true),
+            ("1.5.0", "*", true),
+            ("1.5.0", "1.2", true),
+            ("2.0.0", "1.2", false),
+            ("1.2.9", "~1.2.3", true),
+            ("1.3.0", "~1.2.3", false),
+            ("1.9", ">=1.0, <2", true),
+            ("2.0", ">=1.0, <2", false),
+            ("v1.4.0", "=1.4.0", true),
+            ("1.4.1", "=1.4.0", false),
+            ("1.70", ">= 1.70", true),
+            ("1.69.0", ">= 1.70", false),
+            // pre-releases only match constraints that name one
+            ("2.0.0-beta", ">=1.0", false),
+            ("2.0.0-beta", ">=2.0.0-alpha", true),
+            // not semver: only `*` or the same version
+            ("2024.1.beta", "=2024.1.beta", true),
+            ("2024.1.beta", "2024.1.alpha", false),
+        ];
+        for (version, req, expected) in cases {
+            assert_eq!(
+                satisfies(version, req),
+                expected,
+                "satisfies({:?}, {:?})",
+                version,
+                req
+            );
+        }
+    }
+}
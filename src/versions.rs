@@ -0,0 +1,255 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::{
+    journal::{self, Action},
+    log, Package,
+};
+use colored::*;
+use std::{
+    error::Error,
+    fs, io,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// number of earlier versions of each package kept for `rade rollback`.
+/// Versions installed on purpose and the active version are not counted and never dropped.
+pub const KEEP_VERSIONS: usize = 3;
+
+/// marks a version in `versions/<package>/<version>/` that was installed on purpose,
+/// not left behind by an upgrade.
+const EXPLICIT: &str = "explicit";
+
+impl Package {
+    /// # retain_version
+    /// move the freshly installed executable to `versions/<package>/<version>/`,
+    /// point `bin/<exe>` at it and keep a copy of its install log next to it.
+    /// `explicit` versions were asked for and stay until removed; of the versions
+    /// left behind by upgrades, the oldest beyond `KEEP_VERSIONS` are dropped.
+    pub fn retain_version(package: &str, explicit: bool) -> Result<(), Box<dyn Error>> {
+        let home = Package::rade_home();
+        let (exe, version, _repo) = Package::log_parse(package)?;
+        let dir = home.join("versions/").join(package).join(&version);
+        let bin = home.join("bin/").join(&exe);
+        fs::create_dir_all(&dir)?;
+        if !bin.is_symlink() {
+            fs::rename(&bin, dir.join(&exe))?;
+            link_bin(&dir.join(&exe), &bin)?;
+        }
+        fs::copy(home.join("log/install/").join(package), dir.join("log"))?;
+        if explicit {
+            fs::write(dir.join(EXPLICIT), "")?;
+        }
+
+        let active = Package::installed_version(package);
+        for (_, path) in Package::retained_versions(package)
+            .into_iter()
+            .filter(|(v, path)| Some(v) != active.as_ref() && !path.join(EXPLICIT).exists())
+            .skip(KEEP_VERSIONS)
+        {
+            fs::remove_dir_all(path)?;
+        }
+        Ok(())
+    }
+
+    /// # retained_versions
+    /// versions kept for `package` as `(version, directory)`, most recently installed first.
+    pub fn retained_versions(package: &str) -> Vec<(String, PathBuf)> {
+        let base = Package::rade_home().join("versions/").join(package);
+        let mut versions: Vec<(SystemTime, String, PathBuf)> = match fs::read_dir(base) {
+            Ok(dir) => dir
+                .flatten()
+                .filter_map(|entry| {
+                    let installed = entry.path().join("log").metadata().ok()?.modified().ok()?;
+                    let version = entry.file_name().into_string().ok()?;
+                    Some((installed, version, entry.path()))
+                })
+                .collect(),
+            Err(_) => Vec::new(),
+        };
+        versions.sort_by_key(|v| std::cmp::Reverse(v.0));
+        versions.into_iter().map(|(_, v, p)| (v, p)).collect()
+    }
+
+    /// # rollback
+    /// restore a retained version of the package.
+    /// Without `version`, the version installed before the current one is used.
+    pub fn rollback(package: &str, version: Option<String>) {
+        let current = match Package::installed_version(package) {
+            Some(v) => v,
+            None => {
+                eprintln!(
+                    "{} {}{}",
+                    ">>>".red().bold(),
+                    "Package is not installed: ".bold(),
+                    package.bold()
+                );
+                std::process::exit(1);
+            }
+        };
+        let retained = Package::retained_versions(package);
+        let current_pos = retained.iter().position(|(v, _)| *v == current);
+        let target = match version {
+            Some(version) => retained.into_iter().find(|(v, _)| *v == version),
+            None => retained
+                .into_iter()
+                .enumerate()
+                .find(|(i, (v, _))| *v != current && current_pos.is_none_or(|c| *i > c))
+                .map(|(_, t)| t),
+        };
+        let (version, _) = match target {
+            Some(t) => t,
+            None => {
+                eprintln!(
+                    "{} {}",
+                    ">>>".red().bold(),
+                    "No retained version to roll back to.".bold()
+                );
+                let kept: Vec<String> = Package::retained_versions(package)
+                    .into_iter()
+                    .map(|(v, _)| v)
+                    .collect();
+                eprintln!("Retained versions: {:?}", kept);
+                std::process::exit(1);
+            }
+        };
+        if version == current {
+            println!("{} {} is already active", ">>>".yellow().bold(), version);
+            return;
+        }
+
+        println!(
+            "{} {} {} {} {}",
+            ">>>".green().bold(),
+            "Rolling back".bold(),
+            package.bold(),
+            current,
+            format!("→ {}", version).green().bold()
+        );
+        if let Err(e) = Package::use_version(package, &version) {
+            let _ = journal::Entry::new(Action::Rollback, package)
+                .before(&current)
+                .after(&version)
+                .failed(&e.to_string())
+                .record();
+            eprintln!("{} Failed to roll back {}", ">>>".red().bold(), package);
+            eprintln!("Please report this issue to the comrade repository");
+            eprintln!("Error code: {}", e);
+            std::process::exit(1);
+        }
+        let _ = journal::Entry::new(Action::Rollback, package)
+            .before(&current)
+            .after(&version)
+            .record();
+        println!("{}", "All done!".bold());
+    }
+
+    /// # switch
+    /// `rade use <package>@<version>`: make an installed version the active one.
+    pub fn switch(spec: &str) {
+        let (package, version) = match spec.split_once('@') {
+            Some((p, v)) if !p.is_empty() && !v.is_empty() => (p, v),
+            _ => {
+                eprintln!(
+                    "{} {}",
+                    ">>>".red().bold(),
+                    "Expected <package>@<version>".bold()
+                );
+                std::process::exit(1);
+            }
+        };
+        let current = match Package::installed_version(package) {
+            Some(v) => v,
+            None => {
+                eprintln!(
+                    "{} {}{}",
+                    ">>>".red().bold(),
+                    "Package is not installed: ".bold(),
+                    package.bold()
+                );
+                std::process::exit(1);
+            }
+        };
+        if current == version {
+            println!("{} {} is already active", ">>>".yellow().bold(), version);
+            return;
+        }
+        if let Err(e) = Package::use_version(package, version) {
+            let _ = journal::Entry::new(Action::Use, package)
+                .before(&current)
+                .after(version)
+                .failed(&e.to_string())
+                .record();
+            eprintln!("{} Failed to switch {}", ">>>".red().bold(), package);
+            eprintln!("Error code: {}", e);
+            let kept: Vec<String> = Package::retained_versions(package)
+                .into_iter()
+                .map(|(v, _)| v)
+                .collect();
+            eprintln!("Installed versions: {:?}", kept);
+            std::process::exit(1);
+        }
+        let _ = journal::Entry::new(Action::Use, package)
+            .before(&current)
+            .after(version)
+            .record();
+        // a version picked by hand is kept like one installed by hand
+        let _ = fs::write(
+            Package::rade_home()
+                .join("versions/")
+                .join(package)
+                .join(version)
+                .join(EXPLICIT),
+            "",
+        );
+        println!(
+            "{} {} {} {}",
+            ">>>".green().bold(),
+            "Now using".bold(),
+            package.bold(),
+            version.green().bold()
+        );
+    }
+
+    /// # use_version
+    /// make a retained version the active one: `bin/<exe>` and the install log are switched to it.
+    pub fn use_version(package: &str, version: &str) -> Result<(), Box<dyn Error>> {
+        let home = Package::rade_home();
+        let dir = home.join("versions/").join(package).join(version);
+        if !dir.join("log").exists() {
+            return Err(format!("{} {} is not installed", package, version).into());
+        }
+        let (current_exe, _, _) = Package::log_parse(package)?;
+        let current_bin = home.join("bin/").join(&current_exe);
+        if current_bin.exists() && !current_bin.is_symlink() {
+            // installed before versions were kept side by side
+            Package::retain_version(package, true)?;
+        }
+        let reason = Package::read_install_record(package)?.reason;
+        let log = fs::read_to_string(dir.join("log"))?;
+        if current_bin.is_symlink() || current_bin.exists() {
+            fs::remove_file(&current_bin)?;
+        }
+        fs::write(home.join("log/install/").join(package), log)?;
+        // the reason belongs to the package, not to the version
+        log::Name::new(&home.join("log/install/")).set_reason(package, reason)?;
+        let (exe, _, _) = Package::log_parse(package)?;
+        link_bin(&dir.join(&exe), &home.join("bin/").join(&exe))?;
+        Ok(())
+    }
+}
+
+/// point `link` at the executable `target`.
+/// Windows needs extra privileges for symlinks, so the file is copied there.
+pub fn link_bin(target: &Path, link: &Path) -> io::Result<()> {
+    #[cfg(unix)]
+    {
+        std::os::unix::fs::symlink(target, link)
+    }
+    #[cfg(not(unix))]
+    {
+        fs::copy(target, link).map(|_| ())
+    }
+}
@@ -0,0 +1,125 @@
+// SPDX-License-Identifier: MIT
+// Copyright (c) 2024 17do
+// This software is licensed under the MIT License.
+
+use crate::Package;
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+};
+
+/// `true` if `path` is a file this user may run.
+fn is_executable(path: &Path) -> bool {
+    let Ok(metadata) = fs::metadata(path) else {
+        return false;
+    };
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        metadata.is_file() && metadata.permissions().mode() & 0o111 != 0
+    }
+    #[cfg(not(unix))]
+    {
+        metadata.is_file()
+    }
+}
+
+/// file names `command` may have: on Windows `git` is also `git.exe`, `git.cmd`, ...
+fn candidates(command: &str) -> Vec<String> {
+    if !cfg!(windows) || Path::new(command).extension().is_some() {
+        return vec![command.to_string()];
+    }
+    let pathext = env::var("PATHEXT").unwrap_or_else(|_| ".COM;.EXE;.BAT;.CMD".to_string());
+    pathext
+        .split(';')
+        .filter(|ext| !ext.is_empty())
+        .map(|ext| format!("{}{}", command, ext.to_lowercase()))
+        .collect()
+}
+
+/// every match of `command` in PATH, in PATH order.
+fn find_all(command: &str) -> Vec<PathBuf> {
+    if command.contains(['/', '\\']) {
+        let path = PathBuf::from(command);
+        return if is_executable(&path) {
+            vec![path]
+        } else {
+            Vec::new()
+        };
+    }
+    let Some(path) = env::var_os("PATH") else {
+        return Vec::new();
+    };
+    env::split_paths(&path)
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .flat_map(|dir| {
+            candidates(command)
+                .into_iter()
+                .map(move |name| dir.join(name))
+        })
+        .filter(|path| is_executable(path))
+        .collect()
+}
+
+/// # find
+/// the executable PATH runs for `command`, like `which`.
+pub fn find(command: &str) -> Option<PathBuf> {
+    find_all(command).into_iter().next()
+}
+
+/// `true` if `path` is in rade's `bin/`.
+pub fn is_rade(path: &Path) -> bool {
+    let bin = Package::rade_home().join("bin");
+    let bin = fs::canonicalize(&bin).unwrap_or(bin);
+    path.parent()
+        .map(|dir| fs::canonicalize(dir).unwrap_or(dir.to_path_buf()))
+        .is_some_and(|dir| dir == bin)
+}
+
+/// # find_system
+/// `command` from PATH, skipping the copies rade installed.
+pub fn find_system(command: &str) -> Option<PathBuf> {
+    find_all(command).into_iter().find(|path| !is_rade(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// a scratch directory with an executable `tool` and a plain file `notes`.
+    fn scratch(name: &str) -> PathBuf {
+        let dir = env::temp_dir().join(format!("rade-which-{}-{}", name, std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("tool"), "#!/bin/sh\n").unwrap();
+        fs::write(dir.join("notes"), "").unwrap();
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            fs::set_permissions(dir.join("tool"), fs::Permissions::from_mode(0o755)).unwrap();
+            fs::set_permissions(dir.join("notes"), fs::Permissions::from_mode(0o644)).unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn find_takes_paths_as_they_are() {
+        let dir = scratch("find");
+        let tool = dir.join("tool");
+        assert_eq!(find(&tool.display().to_string()), Some(tool.clone()));
+        assert_eq!(find_system(&tool.display().to_string()), Some(tool));
+        assert_eq!(find(&dir.join("missing").display().to_string()), None);
+        #[cfg(unix)]
+        assert_eq!(find(&dir.join("notes").display().to_string()), None);
+        fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn is_rade_checks_the_directory() {
+        let bin = Package::rade_home().join("bin");
+        assert!(is_rade(&bin.join("hello")));
+        assert!(!is_rade(&bin.join("sub").join("hello")));
+        let dir = scratch("is-rade");
+        assert!(!is_rade(&dir.join("tool")));
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}